@@ -0,0 +1,455 @@
+//! Tracing of the individual HTTP attempts of a retried call
+//!
+//! Retry middleware re-sends a request through the rest of the middleware stack. Placing
+//! [`OpenAITracingMiddleware`](crate::OpenAITracingMiddleware) *before* the retry
+//! middleware gives one generation span per logical call, recording the output and usage
+//! of the final response only. [`OpenAIAttemptTracingMiddleware`], placed *after* the
+//! retry middleware, then adds an `attempt` child span for every attempt with its status,
+//! error and the backoff delay that preceded it:
+//!
+//! ```rust
+//! use reqwest_middleware::ClientBuilder;
+//! use reqwest_openai_tracing::{OpenAIAttemptTracingMiddleware, OpenAITracingMiddleware};
+//! use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+//!
+//! let policy = ExponentialBackoff::builder().build_with_max_retries(3);
+//! let client = ClientBuilder::new(reqwest::Client::new())
+//!     .with(OpenAITracingMiddleware::new())
+//!     .with(RetryTransientMiddleware::new_with_policy(policy))
+//!     .with(OpenAIAttemptTracingMiddleware::new())
+//!     .build();
+//! ```
+//!
+//! When the retry middleware is placed before the tracing middleware instead, the tracing
+//! middleware can't tell a re-sent request from a new one on its own.
+//! [`OpenAIRetryScopeMiddleware`], placed before the retry middleware, marks the requests
+//! sent through it as one call ([`OpenAIRetryMiddleware`](crate::OpenAIRetryMiddleware)
+//! does so itself). The call then gets a single generation: the first attempt creates it,
+//! and once the request is re-sent every attempt (including the first) is recorded as an
+//! `attempt` child span. The generation, and the root span created for it, only end after
+//! the last attempt.
+
+use crate::generation::Generation;
+use crate::metrics::request_error_type;
+use http::Extensions;
+use opentelemetry::global::{BoxedSpan, BoxedTracer};
+use opentelemetry::trace::{Span, SpanKind, Status, Tracer};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_semantic_conventions::attribute::{
+    ERROR_TYPE, HTTP_REQUEST_RESEND_COUNT, HTTP_RESPONSE_STATUS_CODE,
+};
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next, Result};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+
+/// Number of HTTP attempts made for a generation
+pub(crate) const OPENAI_REQUEST_ATTEMPTS: &str = "openai.request.attempts";
+/// 1-based number of an attempt
+pub(crate) const OPENAI_ATTEMPT_NUMBER: &str = "openai.attempt.number";
+/// Time in milliseconds between the end of the previous attempt and the start of this one
+pub(crate) const OPENAI_ATTEMPT_BACKOFF_MS: &str = "openai.attempt.backoff_ms";
+
+/// Name of the spans of individual attempts
+const ATTEMPT_SPAN_NAME: &str = "attempt";
+
+/// Tracks the attempts of one generation; shared with inner middleware through the
+/// request extensions
+#[derive(Clone)]
+pub(crate) struct AttemptTracker {
+    tracer: Arc<BoxedTracer>,
+    parent_cx: Context,
+    state: Arc<Mutex<AttemptState>>,
+}
+
+#[derive(Default)]
+struct AttemptState {
+    attempts: u32,
+    last_attempt_end: Option<Instant>,
+    untraced: Option<UntracedAttempt>,
+}
+
+/// An attempt made without a span of its own, recorded as one if the request is re-sent
+struct UntracedAttempt {
+    start: SystemTime,
+    end: SystemTime,
+    attributes: Vec<KeyValue>,
+    status: Status,
+}
+
+impl AttemptTracker {
+    /// Track attempts as children of the generation span held by `cx`
+    pub(crate) fn new(tracer: Arc<BoxedTracer>, cx: Context) -> Self {
+        Self {
+            tracer,
+            parent_cx: cx,
+            state: Arc::default(),
+        }
+    }
+
+    /// Remember an attempt that completed without a span, such as the first attempt
+    /// through the tracing middleware. Should the request be re-sent, it is recorded as
+    /// the first attempt span, with its status code and `error.type` among `attributes`.
+    pub(crate) fn record_untraced(
+        &self,
+        start: SystemTime,
+        attributes: &[KeyValue],
+        status: &Status,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        if state.attempts > 0 {
+            // Attempts are already traced, e.g. by an inner attempt tracing middleware
+            return;
+        }
+        state.untraced = Some(UntracedAttempt {
+            start,
+            end: SystemTime::now(),
+            attributes: attributes
+                .iter()
+                .filter(|kv| matches!(kv.key.as_str(), HTTP_RESPONSE_STATUS_CODE | ERROR_TYPE))
+                .cloned()
+                .collect(),
+            status: status.clone(),
+        });
+        state.last_attempt_end = Some(Instant::now());
+    }
+
+    /// Number of attempts started so far
+    pub(crate) fn attempts(&self) -> u32 {
+        self.state.lock().unwrap().attempts
+    }
+
+    /// Start the span of the next attempt
+    pub(crate) fn start_attempt(&self) -> AttemptSpan {
+        let mut state = self.state.lock().unwrap();
+        if let Some(untraced) = state.untraced.take() {
+            state.attempts += 1;
+            let mut attributes = vec![KeyValue::new(OPENAI_ATTEMPT_NUMBER, state.attempts as i64)];
+            attributes.extend(untraced.attributes);
+            let mut span = self
+                .tracer
+                .span_builder(ATTEMPT_SPAN_NAME)
+                .with_kind(SpanKind::Client)
+                .with_start_time(untraced.start)
+                .with_attributes(attributes)
+                .start_with_context(self.tracer.as_ref(), &self.parent_cx);
+            span.set_status(untraced.status);
+            span.end_with_timestamp(untraced.end);
+        }
+        state.attempts += 1;
+
+        let mut attributes = vec![KeyValue::new(OPENAI_ATTEMPT_NUMBER, state.attempts as i64)];
+        if state.attempts > 1 {
+            attributes.push(KeyValue::new(
+                HTTP_REQUEST_RESEND_COUNT,
+                (state.attempts - 1) as i64,
+            ));
+        }
+        if let Some(last_end) = state.last_attempt_end {
+            attributes.push(KeyValue::new(
+                OPENAI_ATTEMPT_BACKOFF_MS,
+                last_end.elapsed().as_millis() as i64,
+            ));
+        }
+
+        let span = self
+            .tracer
+            .span_builder(ATTEMPT_SPAN_NAME)
+            .with_kind(SpanKind::Client)
+            .with_attributes(attributes)
+            .start_with_context(self.tracer.as_ref(), &self.parent_cx);
+        AttemptSpan {
+            span,
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// The span of a single attempt
+pub(crate) struct AttemptSpan {
+    span: BoxedSpan,
+    state: Arc<Mutex<AttemptState>>,
+}
+
+impl AttemptSpan {
+    /// Add an event to the span of the attempt
+    pub(crate) fn add_event(&mut self, name: &'static str, attributes: Vec<KeyValue>) {
+        self.span.add_event(name, attributes);
+    }
+
+    /// Record the outcome of the attempt and end its span
+    pub(crate) fn finish(mut self, result: &Result<Response>) {
+        match result {
+            Ok(response) => {
+                let status = response.status();
+                self.span.set_attribute(KeyValue::new(
+                    HTTP_RESPONSE_STATUS_CODE,
+                    status.as_u16() as i64,
+                ));
+                if status.is_success() {
+                    self.span.set_status(Status::Ok);
+                } else {
+                    self.span
+                        .set_attribute(KeyValue::new(ERROR_TYPE, status.as_u16().to_string()));
+                    self.span
+                        .set_status(Status::error(format!("HTTP {}", status)));
+                }
+            }
+            Err(e) => {
+                self.span
+                    .set_attribute(KeyValue::new(ERROR_TYPE, request_error_type(e)));
+                self.span
+                    .set_status(Status::error(format!("Request failed: {}", e)));
+            }
+        }
+        self.span.end();
+        self.state.lock().unwrap().last_attempt_end = Some(Instant::now());
+    }
+}
+
+/// The requests of one call, re-sent by a retry middleware; holds the generation of the
+/// call, which therefore ends once the retry middleware is done with the request
+#[derive(Clone, Default)]
+pub(crate) struct RetryScope(Arc<Mutex<Option<Generation>>>);
+
+impl RetryScope {
+    /// Open a scope for the request of `extensions` unless one is already open; returns
+    /// whether it was opened, in which case the caller must [`close`](Self::close) it
+    pub(crate) fn open(extensions: &mut Extensions) -> bool {
+        if extensions.get::<RetryScope>().is_some() {
+            return false;
+        }
+        extensions.insert(RetryScope::default());
+        true
+    }
+
+    pub(crate) fn close(extensions: &mut Extensions) {
+        extensions.remove::<RetryScope>();
+    }
+
+    /// The generation of an earlier attempt of the call, if any
+    pub(crate) fn generation(&self) -> Option<Generation> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set_generation(&self, generation: Generation) {
+        *self.0.lock().unwrap() = Some(generation);
+    }
+}
+
+/// Middleware marking the requests sent through it as one call, so that a retry
+/// middleware placed after it and before
+/// [`OpenAITracingMiddleware`](crate::OpenAITracingMiddleware) has its attempts recorded
+/// as one generation
+///
+/// ```rust
+/// use reqwest_middleware::ClientBuilder;
+/// use reqwest_openai_tracing::{OpenAIRetryScopeMiddleware, OpenAITracingMiddleware};
+/// use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+///
+/// let policy = ExponentialBackoff::builder().build_with_max_retries(3);
+/// let client = ClientBuilder::new(reqwest::Client::new())
+///     .with(OpenAIRetryScopeMiddleware::new())
+///     .with(RetryTransientMiddleware::new_with_policy(policy))
+///     .with(OpenAITracingMiddleware::new())
+///     .build();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OpenAIRetryScopeMiddleware;
+
+impl OpenAIRetryScopeMiddleware {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for OpenAIRetryScopeMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let opened = RetryScope::open(extensions);
+        let result = next.run(req, extensions).await;
+        if opened {
+            RetryScope::close(extensions);
+        }
+        result
+    }
+}
+
+/// Middleware that records every attempt of a retried call as a child span of its
+/// generation. Place it after the retry middleware, with
+/// [`OpenAITracingMiddleware`](crate::OpenAITracingMiddleware) before it; without an
+/// enclosing generation it does nothing.
+#[derive(Clone, Debug, Default)]
+pub struct OpenAIAttemptTracingMiddleware;
+
+impl OpenAIAttemptTracingMiddleware {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for OpenAIAttemptTracingMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let Some(tracker) = extensions.get::<AttemptTracker>().cloned() else {
+            return next.run(req, extensions).await;
+        };
+
+        let attempt = tracker.start_attempt();
+        let result = next.run(req, extensions).await;
+        attempt.finish(&result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+    use opentelemetry_sdk::export::trace::SpanData;
+    use opentelemetry_sdk::testing::trace::{InMemorySpanExporter, InMemorySpanExporterBuilder};
+    use opentelemetry_sdk::trace::TracerProvider;
+
+    /// A tracker for the attempts of a generation span, and the exporter of both
+    fn tracker() -> (
+        AttemptTracker,
+        Context,
+        TracerProvider,
+        InMemorySpanExporter,
+    ) {
+        let exporter = InMemorySpanExporterBuilder::new().build();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer = Arc::new(BoxedTracer::new(Box::new(provider.tracer("test"))));
+        let cx = Context::new().with_span(tracer.start("generation"));
+        let tracker = AttemptTracker::new(tracer, cx.clone());
+        (tracker, cx, provider, exporter)
+    }
+
+    fn response(status: u16) -> Result<Response> {
+        Ok(Response::from(
+            http::Response::builder().status(status).body("").unwrap(),
+        ))
+    }
+
+    fn attribute(span: &SpanData, key: &str) -> Option<opentelemetry::Value> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    }
+
+    #[test]
+    fn test_attempts_record_outcome_and_backoff() {
+        let (tracker, cx, _provider, exporter) = tracker();
+        tracker.start_attempt().finish(&response(503));
+        tracker
+            .start_attempt()
+            .finish(&Err(reqwest_middleware::Error::middleware(
+                std::io::Error::other("connection reset"),
+            )));
+        tracker.start_attempt().finish(&response(200));
+        assert_eq!(tracker.attempts(), 3);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 3);
+        for (number, span) in (1i64..).zip(&spans) {
+            assert_eq!(span.name, ATTEMPT_SPAN_NAME);
+            assert_eq!(span.parent_span_id, cx.span().span_context().span_id());
+            assert_eq!(attribute(span, OPENAI_ATTEMPT_NUMBER), Some(number.into()));
+            let resends = (number > 1).then(|| (number - 1).into());
+            assert_eq!(attribute(span, HTTP_REQUEST_RESEND_COUNT), resends);
+            // Only re-sent attempts waited for a previous one
+            assert_eq!(
+                attribute(span, OPENAI_ATTEMPT_BACKOFF_MS).is_some(),
+                number > 1
+            );
+        }
+
+        assert_eq!(
+            attribute(&spans[0], HTTP_RESPONSE_STATUS_CODE),
+            Some(503i64.into())
+        );
+        assert_eq!(attribute(&spans[0], ERROR_TYPE), Some("503".into()));
+        assert_eq!(
+            spans[0].status,
+            Status::error("HTTP 503 Service Unavailable")
+        );
+        assert_eq!(attribute(&spans[1], HTTP_RESPONSE_STATUS_CODE), None);
+        assert_eq!(attribute(&spans[1], ERROR_TYPE), Some("_OTHER".into()));
+        assert!(matches!(spans[1].status, Status::Error { .. }));
+        assert_eq!(
+            attribute(&spans[2], HTTP_RESPONSE_STATUS_CODE),
+            Some(200i64.into())
+        );
+        assert_eq!(attribute(&spans[2], ERROR_TYPE), None);
+        assert_eq!(spans[2].status, Status::Ok);
+    }
+
+    #[test]
+    fn test_untraced_attempt_is_recorded_once_resent() {
+        let (tracker, cx, _provider, exporter) = tracker();
+        let start = SystemTime::now();
+        tracker.record_untraced(
+            start,
+            &[
+                KeyValue::new(HTTP_RESPONSE_STATUS_CODE, 429),
+                KeyValue::new(ERROR_TYPE, "rate_limit_exceeded"),
+                KeyValue::new("openai.ratelimit.remaining_requests", 0),
+            ],
+            &Status::error("Rate limit reached"),
+        );
+        // Without a resend, nothing is recorded
+        assert_eq!(tracker.attempts(), 0);
+        assert!(exporter.get_finished_spans().unwrap().is_empty());
+
+        tracker.start_attempt().finish(&response(200));
+        assert_eq!(tracker.attempts(), 2);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 2);
+        let first = &spans[0];
+        assert_eq!(first.parent_span_id, cx.span().span_context().span_id());
+        assert_eq!(first.start_time, start);
+        assert!(first.end_time <= spans[1].start_time);
+        assert_eq!(attribute(first, OPENAI_ATTEMPT_NUMBER), Some(1i64.into()));
+        assert_eq!(
+            attribute(first, ERROR_TYPE),
+            Some("rate_limit_exceeded".into())
+        );
+        // Only the status code and error type of the response are kept
+        assert_eq!(
+            attribute(first, "openai.ratelimit.remaining_requests"),
+            None
+        );
+        assert_eq!(first.status, Status::error("Rate limit reached"));
+        assert_eq!(
+            attribute(&spans[1], OPENAI_ATTEMPT_NUMBER),
+            Some(2i64.into())
+        );
+        assert!(attribute(&spans[1], OPENAI_ATTEMPT_BACKOFF_MS).is_some());
+    }
+
+    #[test]
+    fn test_untraced_attempt_ignored_once_attempts_are_traced() {
+        let (tracker, _cx, _provider, exporter) = tracker();
+        tracker.start_attempt().finish(&response(200));
+        tracker.record_untraced(SystemTime::now(), &[], &Status::Ok);
+        tracker.start_attempt().finish(&response(200));
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(tracker.attempts(), 2);
+    }
+}
@@ -34,6 +34,9 @@ impl LangfuseAttributes {
     pub const OBSERVATION_MODEL_PARAMETERS: &'static str = "langfuse.observation.model.parameters";
     pub const OBSERVATION_USAGE_TOTAL: &'static str = "langfuse.observation.usage.total";
     pub const OBSERVATION_USAGE_DETAILS: &'static str = "langfuse.observation.usage_details";
+    pub const OBSERVATION_COST_DETAILS: &'static str = "langfuse.observation.cost_details";
+    pub const OBSERVATION_COMPLETION_START_TIME: &'static str =
+        "langfuse.observation.completion_start_time";
     pub const OBSERVATION_PROMPT_NAME: &'static str = "langfuse.observation.prompt.name";
     pub const OBSERVATION_PROMPT_VERSION: &'static str = "langfuse.observation.prompt.version";
 
@@ -214,3 +217,122 @@ impl ObservationAttributesBuilder {
         self.attributes
     }
 }
+
+/// Trace attributes for a single request.
+///
+/// Attach it to a request through reqwest-middleware extensions (or
+/// [`HttpClientWithMiddleware::with_trace_attributes`](crate::HttpClientWithMiddleware::with_trace_attributes))
+/// to set one-off attributes without mutating a shared [`LangfuseContext`](crate::LangfuseContext).
+/// Attributes set here override the same attributes from the context.
+#[derive(Clone, Debug, Default)]
+pub struct RequestTraceAttributes {
+    name: Option<String>,
+    user_id: Option<String>,
+    session_id: Option<String>,
+    tags: Option<Vec<String>>,
+    metadata: Option<Value>,
+    prompt_name: Option<String>,
+    prompt_version: Option<String>,
+}
+
+impl RequestTraceAttributes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self
+    }
+
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Link the generation to a Langfuse prompt
+    pub fn with_prompt(mut self, name: impl Into<String>, version: Option<String>) -> Self {
+        self.prompt_name = Some(name.into());
+        self.prompt_version = version;
+        self
+    }
+
+    /// The trace name, if set
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Attributes applied to both the trace and the generation span
+    pub fn trace_attributes(&self) -> Vec<KeyValue> {
+        let mut builder = TraceAttributesBuilder::new();
+        if let Some(name) = &self.name {
+            builder = builder.with_name(name.clone());
+        }
+        if let Some(user_id) = &self.user_id {
+            builder = builder.with_user_id(user_id.clone());
+        }
+        if let Some(session_id) = &self.session_id {
+            builder = builder.with_session_id(session_id.clone());
+        }
+        if let Some(tags) = &self.tags {
+            builder = builder.with_tags(tags.clone());
+        }
+        if let Some(metadata) = &self.metadata {
+            builder = builder.with_metadata(metadata.clone());
+        }
+        builder.build()
+    }
+
+    /// The name and version of the prompt linked with [`with_prompt`](Self::with_prompt)
+    pub(crate) fn prompt(&self) -> Option<(&str, Option<&str>)> {
+        let name = self.prompt_name.as_deref()?;
+        Some((name, self.prompt_version.as_deref()))
+    }
+
+    /// Attributes only applied to the generation span
+    pub fn observation_attributes(&self) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        if let Some(name) = &self.prompt_name {
+            attributes.push(KeyValue::new(
+                LangfuseAttributes::OBSERVATION_PROMPT_NAME,
+                name.clone(),
+            ));
+            if let Some(version) = &self.prompt_version {
+                attributes.push(KeyValue::new(
+                    LangfuseAttributes::OBSERVATION_PROMPT_VERSION,
+                    version.clone(),
+                ));
+            }
+        }
+        attributes
+    }
+}
+
+/// Merge attribute lists, later lists overriding earlier values of the same key
+pub(crate) fn merge_attributes(lists: impl IntoIterator<Item = Vec<KeyValue>>) -> Vec<KeyValue> {
+    let mut merged: Vec<KeyValue> = Vec::new();
+    for list in lists {
+        for kv in list {
+            match merged.iter_mut().find(|existing| existing.key == kv.key) {
+                Some(existing) => *existing = kv,
+                None => merged.push(kv),
+            }
+        }
+    }
+    merged
+}
@@ -0,0 +1,143 @@
+//! Support for the audio endpoints: transcriptions, translations and speech
+//!
+//! Transcriptions and translations upload the audio as a multipart form; only the form
+//! fields and the size of the file are recorded, never the audio itself. Speech requests
+//! are JSON, but their response is audio, of which only the size is recorded.
+
+use crate::multipart::{MultipartFile, MultipartSummary};
+use serde_json::{json, Map, Value};
+
+/// Language of the audio of a transcription (ISO-639-1)
+pub(crate) const AUDIO_LANGUAGE: &str = "openai.audio.language";
+/// Size in bytes of the uploaded audio file
+pub(crate) const AUDIO_FILE_SIZE: &str = "openai.audio.file_size_bytes";
+/// Voice used to generate speech
+pub(crate) const AUDIO_VOICE: &str = "openai.audio.voice";
+/// Size in bytes of the generated speech audio
+pub(crate) const AUDIO_OUTPUT_SIZE: &str = "openai.audio.output_size_bytes";
+
+/// Form fields of a transcription/translation recorded as observation input
+const TRANSCRIPTION_FIELDS: [&str; 5] = [
+    "language",
+    "prompt",
+    "response_format",
+    "temperature",
+    "timestamp_granularities[]",
+];
+
+/// Observation input of a transcription or translation form
+pub(crate) fn transcription_input(form: &MultipartSummary) -> Value {
+    let mut input = Map::new();
+    for field in TRANSCRIPTION_FIELDS {
+        if let Some(value) = form.field(field) {
+            input.insert(field.trim_end_matches("[]").to_string(), json!(value));
+        }
+    }
+    if let Some(file) = audio_file(form) {
+        input.insert(
+            "file".to_string(),
+            json!({
+                "filename": file.filename,
+                "content_type": file.content_type,
+                "size_bytes": file.size,
+            }),
+        );
+    }
+    Value::Object(input)
+}
+
+/// Model parameters of a transcription or translation form
+pub(crate) fn transcription_parameters(form: &MultipartSummary) -> Map<String, Value> {
+    let mut parameters = Map::new();
+    if let Some(format) = form.field("response_format") {
+        parameters.insert("response_format".to_string(), json!(format));
+    }
+    parameters
+}
+
+/// The uploaded audio file of a transcription or translation form
+pub(crate) fn audio_file(form: &MultipartSummary) -> Option<&MultipartFile> {
+    form.files.iter().find(|f| f.field == "file")
+}
+
+/// Observation output of a transcription or translation response
+pub(crate) fn transcription_output(response: &Value) -> Option<Value> {
+    let text = response.get("text")?;
+    let mut output = Map::new();
+    output.insert("text".to_string(), text.clone());
+    // verbose_json responses also report the detected language and duration
+    for key in ["language", "duration"] {
+        if let Some(value) = response.get(key) {
+            output.insert(key.to_string(), value.clone());
+        }
+    }
+    Some(Value::Object(output))
+}
+
+/// Observation input of a speech request
+pub(crate) fn speech_input(request: &Value) -> Option<Value> {
+    let mut input = Map::new();
+    for key in ["input", "voice", "instructions", "response_format", "speed"] {
+        if let Some(value) = request.get(key) {
+            input.insert(key.to_string(), value.clone());
+        }
+    }
+    (!input.is_empty()).then_some(Value::Object(input))
+}
+
+/// Observation output of a speech response: the audio's size and type, not its content
+pub(crate) fn speech_output(content_type: Option<&str>, size: usize) -> Value {
+    json!({
+        "audio_bytes": size,
+        "content_type": content_type,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcription_input() {
+        let form = MultipartSummary {
+            fields: vec![
+                ("model".to_string(), "whisper-1".to_string()),
+                ("language".to_string(), "nl".to_string()),
+                ("response_format".to_string(), "verbose_json".to_string()),
+            ],
+            files: vec![MultipartFile {
+                field: "file".to_string(),
+                filename: Some("call.wav".to_string()),
+                content_type: Some("audio/wav".to_string()),
+                size: 2048,
+            }],
+        };
+
+        assert_eq!(
+            transcription_input(&form),
+            json!({
+                "language": "nl",
+                "response_format": "verbose_json",
+                "file": {"filename": "call.wav", "content_type": "audio/wav", "size_bytes": 2048}
+            })
+        );
+        assert_eq!(
+            transcription_parameters(&form),
+            Map::from_iter([("response_format".to_string(), json!("verbose_json"))])
+        );
+        assert_eq!(audio_file(&form).map(|file| file.size), Some(2048));
+    }
+
+    #[test]
+    fn test_speech_input() {
+        let input = speech_input(&json!({
+            "model": "tts-1",
+            "input": "Hello world",
+            "voice": "alloy"
+        }));
+        assert_eq!(
+            input,
+            Some(json!({"input": "Hello world", "voice": "alloy"}))
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Output extraction for chat and text completions
+//!
+//! All choices are recorded, not only the first, so `n > 1` requests and the reason each
+//! choice stopped are visible. Tool call arguments are parsed into JSON when they are
+//! valid, which makes them readable as structured data in Langfuse.
+
+use serde_json::{json, Map, Value};
+
+/// Observation output of a chat completion: every choice with its message, finish reason
+/// and log probabilities
+pub(crate) fn extract_output(response: &Value) -> Option<Value> {
+    let choices = response.get("choices")?.as_array()?;
+    let choices: Vec<Value> = choices
+        .iter()
+        .map(|choice| {
+            let mut output = Map::new();
+            if let Some(index) = choice.get("index") {
+                output.insert("index".to_string(), index.clone());
+            }
+            if let Some(message) = choice.get("message") {
+                output.insert("message".to_string(), simplify_message(message));
+            }
+            output.insert(
+                "finish_reason".to_string(),
+                choice.get("finish_reason").cloned().unwrap_or(Value::Null),
+            );
+            if let Some(logprobs) = choice.get("logprobs").filter(|v| !v.is_null()) {
+                output.insert("logprobs".to_string(), logprobs.clone());
+            }
+            Value::Object(output)
+        })
+        .collect();
+    Some(json!({ "choices": choices }))
+}
+
+/// The finish reason of each choice, in choice order
+pub(crate) fn finish_reasons(response: &Value) -> Vec<String> {
+    response
+        .get("choices")
+        .and_then(|c| c.as_array())
+        .map(|choices| {
+            choices
+                .iter()
+                .filter_map(|choice| choice.get("finish_reason").and_then(|r| r.as_str()))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The message of a choice without null fields, with its tool calls as structured entries
+fn simplify_message(message: &Value) -> Value {
+    let Some(fields) = message.as_object() else {
+        return message.clone();
+    };
+    let mut simplified = Map::new();
+    for (key, value) in fields {
+        if value.is_null() {
+            continue;
+        }
+        let value = match (key.as_str(), value.as_array()) {
+            ("tool_calls", Some(tool_calls)) => {
+                Value::Array(tool_calls.iter().map(structure_tool_call).collect())
+            }
+            _ => value.clone(),
+        };
+        simplified.insert(key.clone(), value);
+    }
+    Value::Object(simplified)
+}
+
+fn structure_tool_call(tool_call: &Value) -> Value {
+    let function = tool_call.get("function");
+    let arguments = function.and_then(|f| f.get("arguments"));
+    // Arguments are a JSON-encoded string; keep the raw string if the model produced invalid JSON
+    let arguments = match arguments.and_then(|a| a.as_str()) {
+        Some(raw) => serde_json::from_str(raw).unwrap_or_else(|_| json!(raw)),
+        None => arguments.cloned().unwrap_or(Value::Null),
+    };
+    json!({
+        "id": tool_call.get("id"),
+        "type": tool_call.get("type").cloned().unwrap_or(json!("function")),
+        "name": function.and_then(|f| f.get("name")),
+        "arguments": arguments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_output_keeps_all_choices() {
+        let response = json!({
+            "choices": [
+                {
+                    "index": 0,
+                    "message": {"role": "assistant", "content": null, "tool_calls": [
+                        {"id": "call_1", "type": "function", "function": {"name": "lookup", "arguments": "{\"q\":\"rust\"}"}},
+                        {"id": "call_2", "type": "function", "function": {"name": "broken", "arguments": "{not json"}}
+                    ]},
+                    "finish_reason": "tool_calls"
+                },
+                {
+                    "index": 1,
+                    "message": {"role": "assistant", "content": null, "refusal": "I can't help with that."},
+                    "finish_reason": "stop",
+                    "logprobs": {"content": []}
+                }
+            ]
+        });
+
+        assert_eq!(
+            extract_output(&response),
+            Some(json!({"choices": [
+                {
+                    "index": 0,
+                    "message": {"role": "assistant", "tool_calls": [
+                        {"id": "call_1", "type": "function", "name": "lookup", "arguments": {"q": "rust"}},
+                        {"id": "call_2", "type": "function", "name": "broken", "arguments": "{not json"}
+                    ]},
+                    "finish_reason": "tool_calls"
+                },
+                {
+                    "index": 1,
+                    "message": {"role": "assistant", "refusal": "I can't help with that."},
+                    "finish_reason": "stop",
+                    "logprobs": {"content": []}
+                }
+            ]}))
+        );
+        assert_eq!(finish_reasons(&response), vec!["tool_calls", "stop"]);
+    }
+}
@@ -0,0 +1,753 @@
+//! Configuration for [`OpenAITracingMiddleware`](crate::OpenAITracingMiddleware)
+//!
+//! The defaults reproduce the behaviour of `OpenAITracingMiddleware::new()`; use
+//! [`OpenAITracingMiddleware::builder`](crate::OpenAITracingMiddleware::builder) to trace
+//! different clients in one process differently.
+
+use crate::attributes::{merge_attributes, LangfuseAttributes};
+use crate::context::{LangfuseContext, GLOBAL_CONTEXT};
+use crate::conventions::{
+    AttributeConvention, ErrorRecord, LangfuseConvention, OpenAIConvention, OtelGenAiConvention,
+    RequestRecord, ResponseRecord, TraceRecord,
+};
+use crate::media::{replace_inline_media, MediaSink};
+use crate::metrics::GenAiMetrics;
+use crate::pricing::PricingTable;
+use crate::redaction::{ContentKind, Redactor};
+use crate::truncation::{serialize_within, truncate_str};
+use opentelemetry::global::{self, BoxedTracer, ObjectSafeTracerProvider};
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry::trace::{Span, Tracer, TracerProvider};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Default name of the tracer used by the middleware
+pub const DEFAULT_TRACER_NAME: &str = "openai-middleware";
+
+/// Response headers recorded by default: rate limits, retry hints, server timing and
+/// request IDs. Entries ending in `*` match every header with that prefix.
+pub const DEFAULT_CAPTURED_HEADERS: &[&str] = &[
+    "x-ratelimit-*",
+    "retry-after",
+    "retry-after-ms",
+    "openai-processing-ms",
+    "x-request-id",
+    "apim-request-id",
+];
+
+/// Environment variable of the GenAI semantic conventions controlling whether message
+/// content is captured
+pub const CAPTURE_MESSAGE_CONTENT_ENV: &str = "OTEL_INSTRUMENTATION_GENAI_CAPTURE_MESSAGE_CONTENT";
+
+/// How much of the request input and response output is recorded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContentCapture {
+    /// Record no input or output; tokens, latency and other metadata are still traced
+    Off,
+    /// Record the structure of input and output (roles, types, tool names, finish reasons,
+    /// counts) with every text replaced by its length
+    MetadataOnly,
+    /// Record input and output in full
+    #[default]
+    Full,
+}
+
+impl ContentCapture {
+    /// The mode set with [`CAPTURE_MESSAGE_CONTENT_ENV`], if any.
+    ///
+    /// `true` selects [`Full`](Self::Full) and `false` [`Off`](Self::Off), as in other
+    /// instrumentations; `metadata` selects [`MetadataOnly`](Self::MetadataOnly). Values
+    /// naming where content goes (`span_only`, `span_and_event`, `event_only`) select
+    /// `Full`, and `no_content` selects `Off`.
+    pub fn from_env() -> Option<Self> {
+        Self::parse(&std::env::var(CAPTURE_MESSAGE_CONTENT_ENV).ok()?)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" | "full" | "span_only" | "span_and_event" | "event_only" => {
+                Some(Self::Full)
+            }
+            "false" | "0" | "off" | "none" | "no_content" => Some(Self::Off),
+            "metadata" | "metadata_only" => Some(Self::MetadataOnly),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for ContentCapture {
+    fn from(capture: bool) -> Self {
+        if capture {
+            Self::Full
+        } else {
+            Self::Off
+        }
+    }
+}
+
+/// Formats the name of a generation span from the operation name (e.g. `chat.completions`)
+/// and the model, when known.
+pub type SpanNameFormatter = Arc<dyn Fn(&str, Option<&str>) -> String + Send + Sync>;
+
+/// Settings of an [`OpenAITracingMiddleware`](crate::OpenAITracingMiddleware)
+#[derive(Clone)]
+pub struct OpenAITracingConfig {
+    pub(crate) tracer_name: Cow<'static, str>,
+    pub(crate) tracer: Option<Arc<BoxedTracer>>,
+    pub(crate) tracer_provider: Option<Arc<dyn ObjectSafeTracerProvider + Send + Sync>>,
+    pub(crate) meter_provider: Option<Arc<dyn MeterProvider + Send + Sync>>,
+    pub(crate) metrics: Option<Arc<GenAiMetrics>>,
+    pub(crate) create_root_span: bool,
+    pub(crate) content_capture: ContentCapture,
+    pub(crate) message_events: bool,
+    pub(crate) max_attribute_length: Option<usize>,
+    pub(crate) attribute_limits: HashMap<String, usize>,
+    pub(crate) span_name_formatter: SpanNameFormatter,
+    pub(crate) context: LangfuseContext,
+    pub(crate) pricing: Option<Arc<PricingTable>>,
+    pub(crate) captured_headers: Vec<Cow<'static, str>>,
+    pub(crate) redactors: Vec<Arc<dyn Redactor>>,
+    pub(crate) media_sink: Option<Arc<dyn MediaSink>>,
+    pub(crate) conventions: Vec<Arc<dyn AttributeConvention>>,
+}
+
+impl Default for OpenAITracingConfig {
+    fn default() -> Self {
+        Self {
+            tracer_name: Cow::Borrowed(DEFAULT_TRACER_NAME),
+            tracer: None,
+            tracer_provider: None,
+            meter_provider: None,
+            metrics: None,
+            create_root_span: true,
+            content_capture: ContentCapture::Full,
+            message_events: false,
+            max_attribute_length: None,
+            attribute_limits: HashMap::new(),
+            span_name_formatter: Arc::new(|operation_name, _model| {
+                format!("OpenAI {}", operation_name)
+            }),
+            // Shares its storage with the global context, so the langfuse_context helpers apply
+            context: GLOBAL_CONTEXT.clone(),
+            pricing: None,
+            captured_headers: DEFAULT_CAPTURED_HEADERS
+                .iter()
+                .map(|header| Cow::Borrowed(*header))
+                .collect(),
+            redactors: Vec::new(),
+            media_sink: None,
+            conventions: vec![
+                Arc::new(LangfuseConvention),
+                Arc::new(OtelGenAiConvention),
+                Arc::new(OpenAIConvention),
+            ],
+        }
+    }
+}
+
+impl OpenAITracingConfig {
+    /// Name of the tracer spans are created with
+    pub fn tracer_name(&self) -> &str {
+        &self.tracer_name
+    }
+
+    /// The instrumentation scope of the tracer: the tracer name, this crate's version and the
+    /// semantic conventions schema URL
+    pub fn instrumentation_scope(&self) -> InstrumentationScope {
+        InstrumentationScope::builder(self.tracer_name.clone())
+            .with_version(env!("CARGO_PKG_VERSION"))
+            .with_schema_url(opentelemetry_semantic_conventions::SCHEMA_URL)
+            .build()
+    }
+
+    /// Resolve the tracer spans are created with.
+    ///
+    /// An explicitly configured tracer takes precedence over a configured provider. Without
+    /// either, a tracer is obtained from the global provider each time, so a global provider
+    /// installed after the middleware was created is still honoured.
+    pub(crate) fn tracer(&self) -> Arc<BoxedTracer> {
+        if let Some(tracer) = &self.tracer {
+            return tracer.clone();
+        }
+        let scope = self.instrumentation_scope();
+        match &self.tracer_provider {
+            Some(provider) => Arc::new(BoxedTracer::new(provider.boxed_tracer(scope))),
+            None => Arc::new(global::tracer_with_scope(scope)),
+        }
+    }
+
+    /// Create the tracer from the configured provider once, rather than on every request
+    pub(crate) fn resolve_tracer(&mut self) {
+        if self.tracer.is_none() {
+            if let Some(provider) = &self.tracer_provider {
+                let tracer = BoxedTracer::new(provider.boxed_tracer(self.instrumentation_scope()));
+                self.tracer = Some(Arc::new(tracer));
+            }
+        }
+    }
+
+    /// Resolve the instruments metrics are recorded with.
+    ///
+    /// Like the tracer, they are obtained from the configured meter provider, or else from
+    /// the global one each time.
+    pub(crate) fn metrics(&self) -> Arc<GenAiMetrics> {
+        if let Some(metrics) = &self.metrics {
+            return metrics.clone();
+        }
+        let scope = self.instrumentation_scope();
+        let meter = match &self.meter_provider {
+            Some(provider) => provider.meter_with_scope(scope),
+            None => global::meter_with_scope(scope),
+        };
+        Arc::new(GenAiMetrics::new(&meter))
+    }
+
+    /// Create the instruments from the configured meter provider once, rather than on every
+    /// request
+    pub(crate) fn resolve_metrics(&mut self) {
+        if self.metrics.is_none() && self.meter_provider.is_some() {
+            self.metrics = Some(self.metrics());
+        }
+    }
+
+    /// Whether a root trace span is created when there is no active parent span
+    pub fn create_root_span(&self) -> bool {
+        self.create_root_span
+    }
+
+    /// How much of the request input and response output is recorded on spans
+    pub fn content_capture(&self) -> ContentCapture {
+        self.content_capture
+    }
+
+    /// Whether request input and response output are recorded on spans in full
+    pub fn capture_content(&self) -> bool {
+        self.content_capture == ContentCapture::Full
+    }
+
+    /// Whether chat completion spans carry a GenAI event per message and choice
+    pub fn message_events(&self) -> bool {
+        self.message_events
+    }
+
+    /// Maximum length in bytes of recorded input/output attributes, if limited
+    pub fn max_attribute_length(&self) -> Option<usize> {
+        self.max_attribute_length
+    }
+
+    /// Maximum length in bytes of a recorded content attribute (e.g.
+    /// `langfuse.observation.input`): its own limit, or else the general one
+    pub fn attribute_limit(&self, key: &str) -> Option<usize> {
+        self.attribute_limits
+            .get(key)
+            .copied()
+            .or(self.max_attribute_length)
+    }
+
+    /// Maximum length in bytes of recorded input or output outside the conventions (e.g.
+    /// in message events): the limit of `langfuse.observation.input` or `.output`
+    pub(crate) fn content_limit(&self, kind: ContentKind) -> Option<usize> {
+        self.attribute_limit(match kind {
+            ContentKind::Input => LangfuseAttributes::OBSERVATION_INPUT,
+            ContentKind::Output => LangfuseAttributes::OBSERVATION_OUTPUT,
+        })
+    }
+
+    /// The conventions request and response attributes are named by
+    pub fn attribute_conventions(&self) -> &[Arc<dyn AttributeConvention>] {
+        &self.conventions
+    }
+
+    /// The context trace attributes are read from when no context is scoped to the current
+    /// task or OpenTelemetry context
+    pub fn context(&self) -> &LangfuseContext {
+        &self.context
+    }
+
+    /// The prices used to compute the cost of calls, if cost calculation is enabled
+    pub fn pricing(&self) -> Option<&PricingTable> {
+        self.pricing.as_deref()
+    }
+
+    /// Names of the response headers recorded on spans
+    pub fn captured_headers(&self) -> &[Cow<'static, str>] {
+        &self.captured_headers
+    }
+
+    /// The context to read trace attributes from for the current request
+    pub(crate) fn active_context(&self) -> LangfuseContext {
+        LangfuseContext::current().unwrap_or_else(|| self.context.clone())
+    }
+
+    /// Format the name of a generation span
+    pub(crate) fn span_name(&self, operation_name: &str, model: Option<&str>) -> String {
+        (self.span_name_formatter)(operation_name, model)
+    }
+
+    /// The recorded form of request input or response output: reduced to metadata if so
+    /// configured, with inline media replaced by placeholders, and redacted; or `None` if
+    /// content is not captured or a redactor suppressed it
+    pub(crate) fn record_content(&self, kind: ContentKind, content: Value) -> Option<Value> {
+        let content = match self.content_capture {
+            ContentCapture::Off => return None,
+            ContentCapture::MetadataOnly => crate::redaction::strip_text(content),
+            ContentCapture::Full => replace_inline_media(content, self.media_sink.as_deref()),
+        };
+        self.redactors
+            .iter()
+            .try_fold(content, |content, redactor| redactor.redact(kind, content))
+    }
+
+    /// The attributes of a trace in every configured convention, and those set under names
+    /// of their own
+    pub(crate) fn trace_attributes(&self, trace: &TraceRecord<'_>) -> Vec<KeyValue> {
+        let attributes = self
+            .conventions
+            .iter()
+            .map(|convention| convention.trace_attributes(trace))
+            .chain([trace.custom_attributes()]);
+        self.limit_attributes(merge_attributes(attributes))
+    }
+
+    /// The attributes of a request in every configured convention
+    pub(crate) fn request_attributes(&self, request: &RequestRecord<'_>) -> Vec<KeyValue> {
+        let attributes = self
+            .conventions
+            .iter()
+            .map(|convention| convention.request_attributes(request));
+        self.limit_attributes(merge_attributes(attributes))
+    }
+
+    /// The attributes of a response in every configured convention
+    pub(crate) fn response_attributes(&self, response: &ResponseRecord<'_>) -> Vec<KeyValue> {
+        let attributes = self
+            .conventions
+            .iter()
+            .map(|convention| convention.response_attributes(response));
+        self.limit_attributes(merge_attributes(attributes))
+    }
+
+    /// The attributes of a failed call in every configured convention
+    pub(crate) fn error_attributes(&self, error: &ErrorRecord<'_>) -> Vec<KeyValue> {
+        let attributes = self
+            .conventions
+            .iter()
+            .map(|convention| convention.error_attributes(error));
+        self.limit_attributes(merge_attributes(attributes))
+    }
+
+    /// Truncate string attributes to their limit. JSON stays valid JSON, and truncated
+    /// attributes are marked with `<key>.truncated` and `<key>.original_size` (in bytes).
+    pub(crate) fn limit_attributes(&self, attributes: Vec<KeyValue>) -> Vec<KeyValue> {
+        let mut limited = Vec::with_capacity(attributes.len());
+        for attribute in attributes {
+            let (key, value) = (attribute.key, attribute.value);
+            match (value, self.attribute_limit(key.as_str())) {
+                (opentelemetry::Value::String(value), Some(limit))
+                    if value.as_str().len() > limit =>
+                {
+                    let truncated = match serde_json::from_str::<Value>(value.as_str()) {
+                        Ok(content) => serialize_within(&content, limit).0,
+                        Err(_) => truncate_str(value.as_str(), limit).into_owned(),
+                    };
+                    limited.push(KeyValue::new(key.clone(), truncated));
+                    limited.push(KeyValue::new(format!("{}.truncated", key), true));
+                    limited.push(KeyValue::new(
+                        format!("{}.original_size", key),
+                        value.as_str().len() as i64,
+                    ));
+                }
+                (value, _) => limited.push(KeyValue::new(key, value)),
+            }
+        }
+        limited
+    }
+}
+
+/// Builder for a configured [`OpenAITracingMiddleware`](crate::OpenAITracingMiddleware)
+///
+/// # Example
+///
+/// ```rust
+/// use reqwest_openai_tracing::{LangfuseContextBuilder, OpenAITracingMiddleware};
+///
+/// let middleware = OpenAITracingMiddleware::builder()
+///     .with_tracer_name("billing-assistant")
+///     .with_root_span(false)
+///     .with_max_attribute_length(16 * 1024)
+///     .with_span_name_formatter(|operation, model| {
+///         format!("{} {}", operation, model.unwrap_or("unknown"))
+///     })
+///     .with_context(LangfuseContextBuilder::new().user_id("billing").build())
+///     .build();
+/// ```
+pub struct OpenAITracingMiddlewareBuilder {
+    config: OpenAITracingConfig,
+}
+
+impl Default for OpenAITracingMiddlewareBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenAITracingMiddlewareBuilder {
+    /// Start from the default configuration, capturing content as set with
+    /// [`CAPTURE_MESSAGE_CONTENT_ENV`], if at all
+    pub fn new() -> Self {
+        Self::from_config(OpenAITracingConfig::default())
+            .with_content_capture_from(ContentCapture::from_env())
+    }
+
+    /// Use the content capture mode read from the environment, if any
+    fn with_content_capture_from(self, capture: Option<ContentCapture>) -> Self {
+        match capture {
+            Some(capture) => self.with_content_capture(capture),
+            None => self,
+        }
+    }
+
+    /// Start from an existing configuration
+    pub fn from_config(config: OpenAITracingConfig) -> Self {
+        Self { config }
+    }
+
+    /// Set the name of the tracer spans are created with
+    pub fn with_tracer_name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.config.tracer_name = name.into();
+        self
+    }
+
+    /// Create spans with this tracer instead of one from the global provider
+    pub fn with_tracer<T, S>(mut self, tracer: T) -> Self
+    where
+        T: Tracer<Span = S> + Send + Sync + 'static,
+        S: Span + Send + Sync + 'static,
+    {
+        self.config.tracer = Some(Arc::new(BoxedTracer::new(Box::new(tracer))));
+        self
+    }
+
+    /// Obtain the tracer from this provider instead of the global one.
+    ///
+    /// The tracer is created with the [instrumentation scope](OpenAITracingConfig::instrumentation_scope)
+    /// of the configuration.
+    pub fn with_tracer_provider<P, T, S>(mut self, provider: P) -> Self
+    where
+        P: TracerProvider<Tracer = T> + Send + Sync + 'static,
+        T: Tracer<Span = S> + Send + Sync + 'static,
+        S: Span + Send + Sync + 'static,
+    {
+        self.config.tracer_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Record metrics with a meter from this provider instead of the global one.
+    ///
+    /// The meter is created with the [instrumentation scope](OpenAITracingConfig::instrumentation_scope)
+    /// of the configuration.
+    pub fn with_meter_provider<P>(mut self, provider: P) -> Self
+    where
+        P: MeterProvider + Send + Sync + 'static,
+    {
+        self.config.meter_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Create a synthetic root trace span when there is no active parent span (default: true)
+    pub fn with_root_span(mut self, create_root_span: bool) -> Self {
+        self.config.create_root_span = create_root_span;
+        self
+    }
+
+    /// Record request input and response output on spans: `true` or
+    /// [`ContentCapture::Full`], `false` or [`ContentCapture::Off`], or
+    /// [`ContentCapture::MetadataOnly`].
+    ///
+    /// Takes precedence over [`CAPTURE_MESSAGE_CONTENT_ENV`]; defaults to `Full`.
+    pub fn with_content_capture(mut self, capture: impl Into<ContentCapture>) -> Self {
+        self.config.content_capture = capture.into();
+        self
+    }
+
+    /// Add an event per request message and response choice to chat completion spans, as
+    /// defined by the GenAI semantic conventions (default: false).
+    ///
+    /// Message content in the events is recorded as configured with
+    /// [`with_content_capture`](Self::with_content_capture) and the redactors.
+    pub fn with_message_events(mut self, message_events: bool) -> Self {
+        self.config.message_events = message_events;
+        self
+    }
+
+    /// Pass request input and response output through this redactor before recording them.
+    ///
+    /// Redactors apply in the order they were added, followed by those of the request's
+    /// [`LangfuseContext`].
+    pub fn with_redactor(mut self, redactor: impl Redactor + 'static) -> Self {
+        self.config.redactors.push(Arc::new(redactor));
+        self
+    }
+
+    /// Hand the inline images and audio of recorded content to this sink, recording the
+    /// reference it returns in their placeholders (default: media is not kept).
+    ///
+    /// See [`DirectoryMediaSink`](crate::DirectoryMediaSink) to keep media on disk.
+    pub fn with_media_sink(mut self, sink: impl MediaSink + 'static) -> Self {
+        self.config.media_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Name trace, request and response attributes by these conventions instead of the
+    /// default [`LangfuseConvention`], [`OtelGenAiConvention`] and [`OpenAIConvention`].
+    ///
+    /// Conventions are applied in order; when several set the same attribute, the later one
+    /// wins.
+    pub fn with_attribute_conventions(
+        mut self,
+        conventions: Vec<Arc<dyn AttributeConvention>>,
+    ) -> Self {
+        self.config.conventions = conventions;
+        self
+    }
+
+    /// Also record request and response attributes by this convention
+    pub fn with_attribute_convention(
+        mut self,
+        convention: impl AttributeConvention + 'static,
+    ) -> Self {
+        self.config.conventions.push(Arc::new(convention));
+        self
+    }
+
+    /// Limit the length in bytes of recorded input/output attributes, and of every other
+    /// string attribute of the [conventions](Self::with_attribute_conventions).
+    ///
+    /// Content over the limit is truncated while keeping it valid JSON, shortening its
+    /// longest strings first, and marked with `<attribute>.truncated` and
+    /// `<attribute>.original_size`.
+    pub fn with_max_attribute_length(mut self, max_length: usize) -> Self {
+        self.config.max_attribute_length = Some(max_length);
+        self
+    }
+
+    /// Limit the length in bytes of one content attribute, e.g.
+    /// `langfuse.observation.input`, overriding the general limit
+    pub fn with_attribute_limit(mut self, key: impl Into<String>, max_length: usize) -> Self {
+        self.config.attribute_limits.insert(key.into(), max_length);
+        self
+    }
+
+    /// Customize the name of generation spans (default: `OpenAI {operation}`)
+    pub fn with_span_name_formatter<F>(mut self, formatter: F) -> Self
+    where
+        F: Fn(&str, Option<&str>) -> String + Send + Sync + 'static,
+    {
+        self.config.span_name_formatter = Arc::new(formatter);
+        self
+    }
+
+    /// Read trace attributes from this context instead of the global one.
+    ///
+    /// A context scoped with [`LangfuseContext::scope`] still takes precedence.
+    pub fn with_context(mut self, context: LangfuseContext) -> Self {
+        self.config.context = context;
+        self
+    }
+
+    /// Compute the cost of each call from these prices (default: no cost calculation).
+    ///
+    /// Calls to models missing from the table are not priced; their model is recorded as
+    /// `openai.pricing.unknown_model` instead.
+    pub fn with_pricing(mut self, pricing: PricingTable) -> Self {
+        self.config.pricing = Some(Arc::new(pricing));
+        self
+    }
+
+    /// Record exactly these response headers (default: [`DEFAULT_CAPTURED_HEADERS`]).
+    ///
+    /// Names are case-insensitive; a name ending in `*` matches every header with that
+    /// prefix. Pass an empty list to record no headers.
+    pub fn with_captured_headers<I, S>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<Cow<'static, str>>,
+    {
+        self.config.captured_headers = headers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Record this response header in addition to those already captured
+    pub fn with_captured_header(mut self, header: impl Into<Cow<'static, str>>) -> Self {
+        self.config.captured_headers.push(header.into());
+        self
+    }
+
+    /// The configuration built so far
+    pub fn config(&self) -> &OpenAITracingConfig {
+        &self.config
+    }
+
+    pub fn build(self) -> crate::OpenAITracingMiddleware {
+        crate::OpenAITracingMiddleware::with_config(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let config = OpenAITracingConfig::default();
+        assert_eq!(config.tracer_name(), DEFAULT_TRACER_NAME);
+        assert!(config.create_root_span());
+        assert!(config.capture_content());
+        assert_eq!(config.max_attribute_length(), None);
+        assert_eq!(
+            config.span_name("chat.completions", Some("gpt-4o")),
+            "OpenAI chat.completions"
+        );
+    }
+
+    #[test]
+    fn test_builder_overrides() {
+        let context = LangfuseContext::new();
+        context.set_user_id("builder-user");
+
+        let builder = OpenAITracingMiddlewareBuilder::new()
+            .with_tracer_name("custom")
+            .with_root_span(false)
+            .with_content_capture(false)
+            .with_span_name_formatter(|operation, model| {
+                format!("{} {}", operation, model.unwrap_or("?"))
+            })
+            .with_context(context);
+        let config = builder.config();
+
+        assert_eq!(config.tracer_name(), "custom");
+        assert!(!config.create_root_span());
+        assert!(!config.capture_content());
+        assert_eq!(config.span_name("embeddings", None), "embeddings ?");
+        assert_eq!(
+            config.context().get_attribute("user.id").as_deref(),
+            Some("builder-user")
+        );
+    }
+
+    #[test]
+    fn test_content_capture_modes() {
+        // The environment only applies through the builder, and the builder wins
+        assert_eq!(
+            OpenAITracingConfig::default().content_capture(),
+            ContentCapture::Full
+        );
+        let config = OpenAITracingMiddlewareBuilder::from_config(OpenAITracingConfig::default())
+            .with_content_capture_from(ContentCapture::parse("false"))
+            .config()
+            .clone();
+        assert_eq!(config.content_capture(), ContentCapture::Off);
+        let config = OpenAITracingMiddlewareBuilder::from_config(OpenAITracingConfig::default())
+            .with_content_capture_from(None)
+            .config()
+            .clone();
+        assert_eq!(config.content_capture(), ContentCapture::Full);
+
+        assert_eq!(ContentCapture::parse("true"), Some(ContentCapture::Full));
+        assert_eq!(ContentCapture::parse(" FALSE "), Some(ContentCapture::Off));
+        assert_eq!(
+            ContentCapture::parse("metadata"),
+            Some(ContentCapture::MetadataOnly)
+        );
+        assert_eq!(ContentCapture::parse("maybe"), None);
+
+        let content = serde_json::json!({"messages": [{"role": "user", "content": "Hello"}]});
+        let config = OpenAITracingMiddlewareBuilder::new()
+            .with_content_capture(ContentCapture::MetadataOnly)
+            .config()
+            .clone();
+        assert!(!config.capture_content());
+        assert_eq!(
+            config.record_content(ContentKind::Input, content.clone()),
+            Some(serde_json::json!({"messages": [{"role": "user", "content": "[5 chars]"}]}))
+        );
+        let config = OpenAITracingMiddlewareBuilder::new()
+            .with_content_capture(false)
+            .config()
+            .clone();
+        assert_eq!(config.record_content(ContentKind::Input, content), None);
+    }
+
+    #[test]
+    fn test_content_attributes_are_truncated_per_attribute() {
+        let config = OpenAITracingMiddlewareBuilder::new()
+            .with_max_attribute_length(64)
+            .with_attribute_limit("langfuse.observation.output", 1024)
+            .config()
+            .clone();
+        let content = serde_json::json!({"text": "é".repeat(100)}).to_string();
+
+        let input = config.limit_attributes(vec![KeyValue::new(
+            "langfuse.observation.input",
+            content.clone(),
+        )]);
+        assert_eq!(input.len(), 3);
+        let recorded = input[0].value.as_str();
+        assert!(recorded.len() <= 64);
+        // Still valid JSON, cut at a character boundary
+        assert!(serde_json::from_str::<Value>(&recorded).is_ok());
+        assert_eq!(
+            input[1],
+            KeyValue::new("langfuse.observation.input.truncated", true)
+        );
+        assert_eq!(
+            input[2],
+            KeyValue::new("langfuse.observation.input.original_size", 211i64)
+        );
+
+        let output =
+            config.limit_attributes(vec![KeyValue::new("langfuse.observation.output", content)]);
+        assert_eq!(output.len(), 1);
+
+        // Plain text is cut rather than parsed
+        let text = config.limit_attributes(vec![KeyValue::new(
+            "llm.output_messages.0.message.content",
+            "word ".repeat(20),
+        )]);
+        assert_eq!(
+            text[0].value.as_str(),
+            format!("{}...[truncated]", "word ".repeat(10))
+        );
+    }
+
+    #[test]
+    fn test_attribute_conventions_are_combined() {
+        let config = OpenAITracingMiddlewareBuilder::new()
+            .with_attribute_conventions(vec![
+                Arc::new(LangfuseConvention),
+                Arc::new(crate::OpenInferenceConvention),
+            ])
+            .config()
+            .clone();
+        let parameters = serde_json::Map::new();
+        let attributes = config.request_attributes(&RequestRecord {
+            model: Some("gpt-4o"),
+            ..RequestRecord::new("chat", &parameters)
+        });
+        let keys: Vec<_> = attributes.iter().map(|kv| kv.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "langfuse.observation.type",
+                "langfuse.observation.model.name",
+                "openinference.span.kind",
+                "llm.system",
+                "llm.provider",
+                "llm.model_name",
+            ]
+        );
+    }
+}
@@ -1,17 +1,28 @@
 //! Langfuse context helpers for setting trace attributes
 //! Similar to the Python SDK's langfuse_context
+//!
+//! Attributes can be set on the process-wide [`GLOBAL_CONTEXT`], or scoped to a single
+//! task with [`LangfuseContext::scope`] (or to an OpenTelemetry [`Context`] with
+//! [`LangfuseContext::attach_to`]) so concurrent requests don't share trace attributes.
 
 #![allow(dead_code)]
 
 use crate::attributes::LangfuseAttributes;
-use opentelemetry::KeyValue;
+use crate::redaction::Redactor;
+use opentelemetry::{Context, KeyValue};
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::{Arc, RwLock};
 
+tokio::task_local! {
+    static TASK_CONTEXT: LangfuseContext;
+}
+
 /// Thread-safe storage for Langfuse context attributes
 #[derive(Clone)]
 pub struct LangfuseContext {
     attributes: Arc<RwLock<HashMap<String, String>>>,
+    redactors: Arc<RwLock<Vec<Arc<dyn Redactor>>>>,
 }
 
 impl LangfuseContext {
@@ -19,6 +30,7 @@ impl LangfuseContext {
     pub fn new() -> Self {
         Self {
             attributes: Arc::new(RwLock::new(HashMap::new())),
+            redactors: Arc::default(),
         }
     }
 
@@ -105,6 +117,18 @@ impl LangfuseContext {
         self
     }
 
+    /// Redact the input and output of the requests traced with this context, after the
+    /// redactors of the middleware
+    pub fn add_redactor(&self, redactor: impl Redactor + 'static) -> &Self {
+        self.redactors.write().unwrap().push(Arc::new(redactor));
+        self
+    }
+
+    /// The redactors added to this context
+    pub(crate) fn redactors(&self) -> Vec<Arc<dyn Redactor>> {
+        self.redactors.read().unwrap().clone()
+    }
+
     /// Clear all attributes
     pub fn clear(&self) {
         let mut attrs = self.attributes.write().unwrap();
@@ -139,6 +163,38 @@ impl LangfuseContext {
         let attrs = self.attributes.read().unwrap();
         attrs.get(key).cloned()
     }
+
+    /// Run a future with this context as the current task's context.
+    ///
+    /// The context stays current across `.await` points of the future, and is only visible
+    /// to it, so concurrent requests can each carry their own trace attributes.
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        TASK_CONTEXT.scope(self, fut).await
+    }
+
+    /// Run a closure with this context as the current task's context
+    pub fn sync_scope<R>(self, f: impl FnOnce() -> R) -> R {
+        TASK_CONTEXT.sync_scope(self, f)
+    }
+
+    /// Return a copy of the OpenTelemetry context carrying this context
+    pub fn attach_to(&self, cx: &Context) -> Context {
+        cx.with_value(self.clone())
+    }
+
+    /// The context scoped to the current task, or else the one carried by the current
+    /// OpenTelemetry context, if any
+    pub fn current() -> Option<LangfuseContext> {
+        TASK_CONTEXT
+            .try_with(|context| context.clone())
+            .ok()
+            .or_else(|| Context::current().get::<LangfuseContext>().cloned())
+    }
+
+    /// The current context (see [`LangfuseContext::current`]), falling back to the global one
+    pub fn current_or_global() -> LangfuseContext {
+        Self::current().unwrap_or_else(|| GLOBAL_CONTEXT.clone())
+    }
 }
 
 impl Default for LangfuseContext {
@@ -172,6 +228,11 @@ pub fn apply_context() {
     GLOBAL_CONTEXT.apply_to_current_span();
 }
 
+/// Helper function to run a future with its own task-local context
+pub async fn with_context<F: Future>(context: LangfuseContext, fut: F) -> F::Output {
+    context.scope(fut).await
+}
+
 /// Builder pattern for fluent API
 pub struct LangfuseContextBuilder {
     context: LangfuseContext,
@@ -215,6 +276,11 @@ impl LangfuseContextBuilder {
         self
     }
 
+    pub fn redactor(self, redactor: impl Redactor + 'static) -> Self {
+        self.context.add_redactor(redactor);
+        self
+    }
+
     pub fn build(self) -> LangfuseContext {
         self.context
     }
@@ -223,3 +289,61 @@ impl LangfuseContextBuilder {
         self.context.apply_to_current_span();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scope_is_visible_across_await_points() {
+        let context = LangfuseContextBuilder::new().user_id("scoped-user").build();
+
+        let user_id = context
+            .scope(async {
+                tokio::task::yield_now().await;
+                LangfuseContext::current()
+                    .and_then(|c| c.get_attribute(LangfuseAttributes::TRACE_USER_ID))
+            })
+            .await;
+
+        assert_eq!(user_id.as_deref(), Some("scoped-user"));
+        assert!(LangfuseContext::current().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_scopes_are_isolated() {
+        let task = |user: &'static str| {
+            LangfuseContextBuilder::new()
+                .user_id(user)
+                .build()
+                .scope(async {
+                    tokio::task::yield_now().await;
+                    LangfuseContext::current()
+                        .and_then(|c| c.get_attribute(LangfuseAttributes::TRACE_USER_ID))
+                })
+        };
+
+        let (a, b) = tokio::join!(task("user-a"), task("user-b"));
+        assert_eq!(a.as_deref(), Some("user-a"));
+        assert_eq!(b.as_deref(), Some("user-b"));
+    }
+
+    #[test]
+    fn test_task_scope_takes_precedence_over_otel_context() {
+        let attached = LangfuseContextBuilder::new().session_id("attached").build();
+        let _guard = attached.attach_to(&Context::current()).attach();
+        assert_eq!(
+            LangfuseContext::current()
+                .and_then(|c| c.get_attribute(LangfuseAttributes::TRACE_SESSION_ID))
+                .as_deref(),
+            Some("attached")
+        );
+
+        let scoped = LangfuseContextBuilder::new().session_id("scoped").build();
+        let session_id = scoped.sync_scope(|| {
+            LangfuseContext::current()
+                .and_then(|c| c.get_attribute(LangfuseAttributes::TRACE_SESSION_ID))
+        });
+        assert_eq!(session_id.as_deref(), Some("scoped"));
+    }
+}
@@ -0,0 +1,1391 @@
+//! Attribute conventions
+//!
+//! Backends expect the same facts about a call under different attribute names: Langfuse
+//! reads `langfuse.observation.*`, Arize Phoenix the OpenInference `llm.input_messages.*`
+//! and `openinference.span.kind`, Traceloop the OpenLLMetry `gen_ai.prompt.N.content`. An
+//! [`AttributeConvention`] turns what the middleware learned about a request and its
+//! response into the attributes of one such convention.
+//!
+//! Built-in conventions:
+//!
+//! - [`LangfuseConvention`]: observation type, model, parameters, input, output, usage and
+//!   cost details, completion start time and error level as read by Langfuse
+//! - [`OtelGenAiConvention`]: the `gen_ai.*` attributes of the OpenTelemetry GenAI
+//!   semantic conventions
+//! - [`OpenAIConvention`]: the details of OpenAI calls without a name in the other
+//!   conventions, such as `openai.stream.*` timings, captured response headers and
+//!   `duration_ms`
+//! - [`OpenInferenceConvention`]: the OpenInference attributes read by Arize Phoenix
+//! - [`OpenLlmetryConvention`]: the OpenLLMetry attributes read by Traceloop
+//!
+//! Conventions are combined with
+//! [`with_attribute_conventions`](crate::OpenAITracingMiddlewareBuilder::with_attribute_conventions);
+//! the default is Langfuse, OpenTelemetry GenAI and OpenAI. When several set the same
+//! attribute, the later one wins. Only the span status, `error.type` and
+//! `http.response.status_code` are recorded whatever the conventions. Input and output are handed to conventions as recorded (see
+//! [`ContentCapture`](crate::ContentCapture) and [`Redactor`](crate::Redactor)), and the
+//! attributes they produce are subject to the configured size limits.
+
+use crate::attempts::OPENAI_REQUEST_ATTEMPTS;
+use crate::attributes::LangfuseAttributes;
+use crate::audio::{AUDIO_FILE_SIZE, AUDIO_LANGUAGE, AUDIO_OUTPUT_SIZE, AUDIO_VOICE};
+use crate::errors::{
+    content_filter_attributes, OPENAI_ERROR_CODE, OPENAI_ERROR_PARAM, OPENAI_ERROR_TYPE,
+};
+use crate::headers::header_attributes;
+use crate::parameters::parameter_attributes;
+use crate::pricing::{Cost, GEN_AI_USAGE_COST, OPENAI_PRICING_UNKNOWN_MODEL};
+use crate::usage::Usage;
+use http::HeaderMap;
+use opentelemetry::{KeyValue, StringValue};
+use opentelemetry_semantic_conventions::attribute::{
+    GEN_AI_OPENAI_RESPONSE_SERVICE_TIER, GEN_AI_OPERATION_NAME, GEN_AI_REQUEST_MODEL,
+    GEN_AI_RESPONSE_FINISH_REASONS, GEN_AI_RESPONSE_ID, GEN_AI_RESPONSE_MODEL, GEN_AI_SYSTEM,
+};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::time::{Duration, SystemTime};
+
+/// Backend configuration fingerprint of the system that generated a response
+const OPENAI_RESPONSE_SYSTEM_FINGERPRINT: &str = "openai.response.system_fingerprint";
+/// Milliseconds from the start of the request to the first output chunk
+pub(crate) const OPENAI_STREAM_TIME_TO_FIRST_TOKEN_MS: &str =
+    "openai.stream.time_to_first_token_ms";
+/// Number of chunks that carried output
+const OPENAI_STREAM_OUTPUT_CHUNKS: &str = "openai.stream.output_chunks";
+/// Mean, minimum and maximum milliseconds between consecutive output chunks
+const OPENAI_STREAM_INTER_TOKEN_LATENCY_MEAN_MS: &str = "openai.stream.inter_token_latency.mean_ms";
+const OPENAI_STREAM_INTER_TOKEN_LATENCY_MIN_MS: &str = "openai.stream.inter_token_latency.min_ms";
+const OPENAI_STREAM_INTER_TOKEN_LATENCY_MAX_MS: &str = "openai.stream.inter_token_latency.max_ms";
+/// Whether the caller dropped the stream before it completed
+const OPENAI_STREAM_CANCELLED: &str = "openai.stream.cancelled";
+/// Milliseconds from the start of the request until the call was done with, including
+/// retries and the consumption of a streamed response
+const DURATION_MS: &str = "duration_ms";
+
+/// What is known about the trace of a call: the attributes of its
+/// [`LangfuseContext`](crate::LangfuseContext) and
+/// [`RequestTraceAttributes`](crate::RequestTraceAttributes), named as Langfuse names them
+#[derive(Clone, Copy, Debug)]
+pub struct TraceRecord<'a> {
+    pub(crate) attributes: &'a [KeyValue],
+}
+
+impl<'a> TraceRecord<'a> {
+    /// Every trace attribute, named as Langfuse names them
+    pub fn attributes(&self) -> &'a [KeyValue] {
+        self.attributes
+    }
+
+    /// The trace name
+    pub fn name(&self) -> Option<Cow<'a, str>> {
+        self.get(LangfuseAttributes::TRACE_NAME)
+    }
+
+    /// The ID of the user the trace belongs to
+    pub fn user_id(&self) -> Option<Cow<'a, str>> {
+        self.get(LangfuseAttributes::TRACE_USER_ID)
+    }
+
+    /// The ID of the session the trace belongs to
+    pub fn session_id(&self) -> Option<Cow<'a, str>> {
+        self.get(LangfuseAttributes::TRACE_SESSION_ID)
+    }
+
+    /// The tags of the trace
+    pub fn tags(&self) -> Vec<String> {
+        self.get(LangfuseAttributes::TRACE_TAGS)
+            .and_then(|tags| serde_json::from_str(&tags).ok())
+            .unwrap_or_default()
+    }
+
+    /// The metadata of the trace
+    pub fn metadata(&self) -> Option<Value> {
+        self.get(LangfuseAttributes::TRACE_METADATA)
+            .and_then(|metadata| serde_json::from_str(&metadata).ok())
+    }
+
+    /// Attributes set under names of their own (e.g. with
+    /// [`LangfuseContext::set_attribute`](crate::LangfuseContext::set_attribute)), which
+    /// are recorded whatever the conventions
+    pub(crate) fn custom_attributes(&self) -> Vec<KeyValue> {
+        self.attributes
+            .iter()
+            .filter(|kv| !is_langfuse_trace_attribute(kv.key.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    fn get(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.as_str())
+    }
+}
+
+fn is_langfuse_trace_attribute(key: &str) -> bool {
+    key.starts_with("langfuse.")
+        || key == LangfuseAttributes::TRACE_USER_ID
+        || key == LangfuseAttributes::TRACE_SESSION_ID
+}
+
+/// What is known about a request when its span starts
+#[derive(Clone, Copy, Debug)]
+pub struct RequestRecord<'a> {
+    pub(crate) operation: &'a str,
+    pub(crate) model: Option<&'a str>,
+    pub(crate) parameters: &'a Map<String, Value>,
+    pub(crate) input: Option<&'a Value>,
+    pub(crate) prompt_name: Option<&'a str>,
+    pub(crate) prompt_version: Option<&'a str>,
+    pub(crate) audio_language: Option<&'a str>,
+    pub(crate) audio_file_size: Option<usize>,
+    pub(crate) voice: Option<&'a str>,
+}
+
+impl<'a> RequestRecord<'a> {
+    /// A request of which only the operation and parameters are known
+    pub(crate) fn new(operation: &'a str, parameters: &'a Map<String, Value>) -> Self {
+        Self {
+            operation,
+            model: None,
+            parameters,
+            input: None,
+            prompt_name: None,
+            prompt_version: None,
+            audio_language: None,
+            audio_file_size: None,
+            voice: None,
+        }
+    }
+
+    /// The operation, e.g. `chat`, `embedding` or `response`
+    pub fn operation(&self) -> &'a str {
+        self.operation
+    }
+
+    /// The requested model or Azure deployment
+    pub fn model(&self) -> Option<&'a str> {
+        self.model
+    }
+
+    /// The model parameters of the request, e.g. `temperature` or `max_tokens`
+    pub fn parameters(&self) -> &'a Map<String, Value> {
+        self.parameters
+    }
+
+    /// The recorded input, e.g. `{"messages": [...]}` for a chat completion; `None` when
+    /// content is not captured
+    pub fn input(&self) -> Option<&'a Value> {
+        self.input
+    }
+
+    /// The name of the prompt the request was made from, see
+    /// [`RequestTraceAttributes::with_prompt`](crate::RequestTraceAttributes::with_prompt)
+    pub fn prompt_name(&self) -> Option<&'a str> {
+        self.prompt_name
+    }
+
+    /// The version of the prompt the request was made from
+    pub fn prompt_version(&self) -> Option<&'a str> {
+        self.prompt_version
+    }
+
+    /// The language of the audio of a transcription (ISO-639-1)
+    pub fn audio_language(&self) -> Option<&'a str> {
+        self.audio_language
+    }
+
+    /// The size in bytes of the audio file uploaded for a transcription or translation
+    pub fn audio_file_size(&self) -> Option<usize> {
+        self.audio_file_size
+    }
+
+    /// The voice speech is generated with
+    pub fn voice(&self) -> Option<&'a str> {
+        self.voice
+    }
+}
+
+/// Mean, minimum and maximum time between consecutive output chunks of a stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InterTokenLatency {
+    pub mean: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+/// What is known about a response. It is recorded in parts as they become known: the
+/// headers of each attempt, the body, the timing of a stream and finally the duration
+/// of the call (also of a failed one).
+#[derive(Clone, Copy, Debug)]
+pub struct ResponseRecord<'a> {
+    pub(crate) operation: &'a str,
+    pub(crate) model: Option<&'a str>,
+    pub(crate) id: Option<&'a str>,
+    pub(crate) finish_reasons: &'a [String],
+    pub(crate) output: Option<&'a Value>,
+    pub(crate) usage: Option<&'a Usage>,
+    pub(crate) cost: Option<&'a Cost>,
+    pub(crate) unpriced_model: Option<&'a str>,
+    pub(crate) system_fingerprint: Option<&'a str>,
+    pub(crate) service_tier: Option<&'a str>,
+    pub(crate) headers: Option<&'a HeaderMap>,
+    pub(crate) audio_output_size: Option<usize>,
+    pub(crate) completion_start_time: Option<SystemTime>,
+    pub(crate) time_to_first_token: Option<Duration>,
+    pub(crate) output_chunks: Option<u64>,
+    pub(crate) inter_token_latency: Option<InterTokenLatency>,
+    pub(crate) attempts: Option<u32>,
+    pub(crate) duration: Option<Duration>,
+}
+
+impl<'a> ResponseRecord<'a> {
+    /// A response of which nothing is known yet
+    pub(crate) fn new(operation: &'a str) -> Self {
+        Self {
+            operation,
+            model: None,
+            id: None,
+            finish_reasons: &[],
+            output: None,
+            usage: None,
+            cost: None,
+            unpriced_model: None,
+            system_fingerprint: None,
+            service_tier: None,
+            headers: None,
+            audio_output_size: None,
+            completion_start_time: None,
+            time_to_first_token: None,
+            output_chunks: None,
+            inter_token_latency: None,
+            attempts: None,
+            duration: None,
+        }
+    }
+
+    /// The operation, e.g. `chat`, `embedding` or `response`
+    pub fn operation(&self) -> &'a str {
+        self.operation
+    }
+
+    /// The model that served the request
+    pub fn model(&self) -> Option<&'a str> {
+        self.model
+    }
+
+    /// The ID of the completion or response
+    pub fn id(&self) -> Option<&'a str> {
+        self.id
+    }
+
+    /// Why each choice stopped, in choice order
+    pub fn finish_reasons(&self) -> &'a [String] {
+        self.finish_reasons
+    }
+
+    /// The recorded output, e.g. `{"choices": [...]}` for a chat completion; `None` when
+    /// content is not captured
+    pub fn output(&self) -> Option<&'a Value> {
+        self.output
+    }
+
+    /// Number of input tokens
+    pub fn input_tokens(&self) -> Option<i64> {
+        self.usage.and_then(|usage| usage.input)
+    }
+
+    /// Number of output tokens
+    pub fn output_tokens(&self) -> Option<i64> {
+        self.usage.and_then(|usage| usage.output)
+    }
+
+    /// Total number of tokens
+    pub fn total_tokens(&self) -> Option<i64> {
+        self.usage.and_then(|usage| usage.total)
+    }
+
+    /// Usage broken down as Langfuse reports it: `input`, `output`, `total` and details
+    /// such as `input_cached_tokens`, each excluded from the plain counts
+    pub fn usage_details(&self) -> Option<&'a Map<String, Value>> {
+        self.usage
+            .map(|usage| &usage.details)
+            .filter(|details| !details.is_empty())
+    }
+
+    /// Cost in USD, broken down like [`usage_details`](Self::usage_details) plus the
+    /// `total`; `None` unless a pricing table prices the model
+    pub fn cost_details(&self) -> Option<&'a Map<String, Value>> {
+        self.cost.map(|cost| &cost.details)
+    }
+
+    /// Total cost in USD
+    pub fn total_cost(&self) -> Option<f64> {
+        self.cost.map(|cost| cost.total)
+    }
+
+    /// A count of [`usage_details`](Self::usage_details), e.g. `input_cached_tokens` or
+    /// `output_reasoning_tokens`
+    pub fn detail(&self, key: &str) -> Option<i64> {
+        self.usage.and_then(|usage| usage.detail(key))
+    }
+
+    /// The model that could not be priced as it is not in the pricing table
+    pub fn unpriced_model(&self) -> Option<&'a str> {
+        self.unpriced_model
+    }
+
+    /// The backend configuration fingerprint of the system that generated the response
+    pub fn system_fingerprint(&self) -> Option<&'a str> {
+        self.system_fingerprint
+    }
+
+    /// The service tier that served the request
+    pub fn service_tier(&self) -> Option<&'a str> {
+        self.service_tier
+    }
+
+    /// The response headers matching
+    /// [`with_captured_headers`](crate::OpenAITracingMiddlewareBuilder::with_captured_headers),
+    /// e.g. rate limits and request IDs, also of error responses
+    pub fn headers(&self) -> Option<&'a HeaderMap> {
+        self.headers
+    }
+
+    /// The size in bytes of generated speech audio
+    pub fn audio_output_size(&self) -> Option<usize> {
+        self.audio_output_size
+    }
+
+    /// When the first output of a streamed response arrived
+    pub fn completion_start_time(&self) -> Option<SystemTime> {
+        self.completion_start_time
+    }
+
+    /// Time from the start of the request to the first output of a streamed response
+    pub fn time_to_first_token(&self) -> Option<Duration> {
+        self.time_to_first_token
+    }
+
+    /// Number of chunks of a streamed response that carried output
+    pub fn output_chunks(&self) -> Option<u64> {
+        self.output_chunks
+    }
+
+    /// Time between consecutive output chunks of a streamed response
+    pub fn inter_token_latency(&self) -> Option<InterTokenLatency> {
+        self.inter_token_latency
+    }
+
+    /// Number of attempts a retry middleware made
+    pub fn attempts(&self) -> Option<u32> {
+        self.attempts
+    }
+
+    /// Time from the start of the request until the call was done with, including retries
+    /// and the consumption of a streamed response
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+}
+
+/// What is known about a call that failed, or a stream the caller dropped before it
+/// completed
+#[derive(Clone, Copy, Debug)]
+pub struct ErrorRecord<'a> {
+    pub(crate) operation: &'a str,
+    pub(crate) message: &'a str,
+    pub(crate) error_type: &'a str,
+    pub(crate) cancelled: bool,
+    pub(crate) code: Option<&'a str>,
+    pub(crate) kind: Option<&'a str>,
+    pub(crate) param: Option<&'a str>,
+    pub(crate) content_filter: Option<&'a Value>,
+}
+
+impl<'a> ErrorRecord<'a> {
+    /// A failure of which nothing but its description is known
+    pub(crate) fn new(operation: &'a str, message: &'a str, error_type: &'a str) -> Self {
+        Self {
+            operation,
+            message,
+            error_type,
+            cancelled: false,
+            code: None,
+            kind: None,
+            param: None,
+            content_filter: None,
+        }
+    }
+
+    /// The operation, e.g. `chat`, `embedding` or `response`
+    pub fn operation(&self) -> &'a str {
+        self.operation
+    }
+
+    /// Human readable description, also the span status message
+    pub fn message(&self) -> &'a str {
+        self.message
+    }
+
+    /// The low-cardinality `error.type`, e.g. an OpenAI error code or `timeout`
+    pub fn error_type(&self) -> &'a str {
+        self.error_type
+    }
+
+    /// Whether the caller cancelled the call, e.g. by dropping its response stream,
+    /// rather than the call failing
+    pub fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// The `code` of an OpenAI error, e.g. `context_length_exceeded`
+    pub fn code(&self) -> Option<&'a str> {
+        self.code
+    }
+
+    /// The `type` of an OpenAI error, e.g. `invalid_request_error`
+    pub fn kind(&self) -> Option<&'a str> {
+        self.kind
+    }
+
+    /// The request parameter an OpenAI error relates to, e.g. `messages`
+    pub fn param(&self) -> Option<&'a str> {
+        self.param
+    }
+
+    /// The results of the Azure OpenAI content filter that rejected a prompt, per
+    /// category, e.g. `{"violence": {"filtered": true, "severity": "high"}}`
+    pub fn content_filter(&self) -> Option<&'a Value> {
+        self.content_filter
+    }
+}
+
+/// Names the attributes a request and its response are recorded with
+pub trait AttributeConvention: Send + Sync {
+    /// Attributes of the trace, set on the root span and the generation span
+    fn trace_attributes(&self, _trace: &TraceRecord<'_>) -> Vec<KeyValue> {
+        Vec::new()
+    }
+
+    /// Attributes of the request, set when its span starts
+    fn request_attributes(&self, request: &RequestRecord<'_>) -> Vec<KeyValue>;
+
+    /// Attributes of a successful response
+    fn response_attributes(&self, response: &ResponseRecord<'_>) -> Vec<KeyValue>;
+
+    /// Attributes of a failed call, in addition to the span status and `error.type`
+    fn error_attributes(&self, _error: &ErrorRecord<'_>) -> Vec<KeyValue> {
+        Vec::new()
+    }
+}
+
+/// The attributes read by [Langfuse](https://langfuse.com/docs/opentelemetry)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LangfuseConvention;
+
+impl AttributeConvention for LangfuseConvention {
+    fn trace_attributes(&self, trace: &TraceRecord<'_>) -> Vec<KeyValue> {
+        trace
+            .attributes
+            .iter()
+            .filter(|kv| is_langfuse_trace_attribute(kv.key.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    fn request_attributes(&self, request: &RequestRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = vec![KeyValue::new(
+            LangfuseAttributes::OBSERVATION_TYPE,
+            "generation",
+        )];
+        if let Some(model) = request.model {
+            attributes.push(KeyValue::new(
+                LangfuseAttributes::OBSERVATION_MODEL,
+                model.to_string(),
+            ));
+        }
+        if !request.parameters.is_empty() {
+            attributes.push(KeyValue::new(
+                LangfuseAttributes::OBSERVATION_MODEL_PARAMETERS,
+                Value::Object(request.parameters.clone()).to_string(),
+            ));
+        }
+        if let Some(input) = request.input {
+            attributes.push(KeyValue::new(
+                LangfuseAttributes::OBSERVATION_INPUT,
+                input.to_string(),
+            ));
+        }
+        if let Some(name) = request.prompt_name {
+            attributes.push(KeyValue::new(
+                LangfuseAttributes::OBSERVATION_PROMPT_NAME,
+                name.to_string(),
+            ));
+            if let Some(version) = request.prompt_version {
+                attributes.push(KeyValue::new(
+                    LangfuseAttributes::OBSERVATION_PROMPT_VERSION,
+                    version.to_string(),
+                ));
+            }
+        }
+        attributes
+    }
+
+    fn response_attributes(&self, response: &ResponseRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        if let Some(output) = response.output {
+            attributes.push(KeyValue::new(
+                LangfuseAttributes::OBSERVATION_OUTPUT,
+                output.to_string(),
+            ));
+        }
+        if let Some(total) = response.total_tokens() {
+            attributes.push(KeyValue::new(
+                LangfuseAttributes::OBSERVATION_USAGE_TOTAL,
+                total,
+            ));
+        }
+        if let Some(details) = response.usage_details() {
+            attributes.push(KeyValue::new(
+                LangfuseAttributes::OBSERVATION_USAGE_DETAILS,
+                Value::Object(details.clone()).to_string(),
+            ));
+        }
+        if let Some(details) = response.cost_details() {
+            attributes.push(KeyValue::new(
+                LangfuseAttributes::OBSERVATION_COST_DETAILS,
+                Value::Object(details.clone()).to_string(),
+            ));
+        }
+        if let Some(time) = response.completion_start_time {
+            attributes.push(KeyValue::new(
+                LangfuseAttributes::OBSERVATION_COMPLETION_START_TIME,
+                chrono::DateTime::<chrono::Utc>::from(time)
+                    .to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            ));
+        }
+        attributes
+    }
+
+    fn error_attributes(&self, error: &ErrorRecord<'_>) -> Vec<KeyValue> {
+        // A cancelled call didn't go wrong, it was cut short
+        let level = match error.cancelled {
+            true => "WARNING",
+            false => "ERROR",
+        };
+        vec![
+            KeyValue::new(LangfuseAttributes::OBSERVATION_LEVEL, level),
+            KeyValue::new(
+                LangfuseAttributes::OBSERVATION_STATUS_MESSAGE,
+                error.message.to_string(),
+            ),
+        ]
+    }
+}
+
+/// The attributes of the [OpenTelemetry GenAI semantic conventions](https://opentelemetry.io/docs/specs/semconv/gen-ai/gen-ai-spans/)
+///
+/// They don't record content on the span; see
+/// [`with_message_events`](crate::OpenAITracingMiddlewareBuilder::with_message_events).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OtelGenAiConvention;
+
+impl AttributeConvention for OtelGenAiConvention {
+    fn trace_attributes(&self, trace: &TraceRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        for (key, value) in [
+            (LangfuseAttributes::TRACE_USER_ID, trace.user_id()),
+            (LangfuseAttributes::TRACE_SESSION_ID, trace.session_id()),
+        ] {
+            if let Some(value) = value {
+                attributes.push(KeyValue::new(key, value.into_owned()));
+            }
+        }
+        attributes
+    }
+
+    fn request_attributes(&self, request: &RequestRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = vec![
+            KeyValue::new(GEN_AI_SYSTEM, "openai"),
+            KeyValue::new(GEN_AI_OPERATION_NAME, request.operation.to_string()),
+        ];
+        if let Some(model) = request.model {
+            attributes.push(KeyValue::new(GEN_AI_REQUEST_MODEL, model.to_string()));
+        }
+        attributes.extend(parameter_attributes(request.parameters));
+        attributes
+    }
+
+    fn response_attributes(&self, response: &ResponseRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        if let Some(model) = response.model {
+            attributes.push(KeyValue::new(GEN_AI_RESPONSE_MODEL, model.to_string()));
+        }
+        if let Some(id) = response.id {
+            attributes.push(KeyValue::new(GEN_AI_RESPONSE_ID, id.to_string()));
+        }
+        if !response.finish_reasons.is_empty() {
+            let finish_reasons: Vec<StringValue> = response
+                .finish_reasons
+                .iter()
+                .cloned()
+                .map(StringValue::from)
+                .collect();
+            attributes.push(KeyValue::new(
+                GEN_AI_RESPONSE_FINISH_REASONS,
+                opentelemetry::Value::Array(finish_reasons.into()),
+            ));
+        }
+        if let Some(usage) = response.usage {
+            attributes.extend(usage.semconv_attributes());
+        }
+        if let Some(cost) = response.total_cost() {
+            attributes.push(KeyValue::new(GEN_AI_USAGE_COST, cost));
+        }
+        if let Some(tier) = response.service_tier {
+            attributes.push(KeyValue::new(
+                GEN_AI_OPENAI_RESPONSE_SERVICE_TIER,
+                tier.to_string(),
+            ));
+        }
+        attributes
+    }
+}
+
+/// The details of OpenAI calls that the other conventions have no name for: the system
+/// fingerprint, audio sizes, stream timings, retry attempts and `duration_ms` of a call,
+/// the captured `http.response.header.*`, the `openai.error.*` fields and Azure
+/// `azure.content_filter.*` results of an error, and an `openai.pricing.unknown_model`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpenAIConvention;
+
+impl AttributeConvention for OpenAIConvention {
+    fn request_attributes(&self, request: &RequestRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        if let Some(language) = request.audio_language {
+            attributes.push(KeyValue::new(AUDIO_LANGUAGE, language.to_string()));
+        }
+        if let Some(size) = request.audio_file_size {
+            attributes.push(KeyValue::new(AUDIO_FILE_SIZE, size as i64));
+        }
+        if let Some(voice) = request.voice {
+            attributes.push(KeyValue::new(AUDIO_VOICE, voice.to_string()));
+        }
+        attributes
+    }
+
+    fn response_attributes(&self, response: &ResponseRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        if let Some(headers) = response.headers {
+            attributes.extend(header_attributes(headers));
+        }
+        for (key, value) in [
+            (
+                OPENAI_RESPONSE_SYSTEM_FINGERPRINT,
+                response.system_fingerprint,
+            ),
+            (OPENAI_PRICING_UNKNOWN_MODEL, response.unpriced_model),
+        ] {
+            if let Some(value) = value {
+                attributes.push(KeyValue::new(key, value.to_string()));
+            }
+        }
+        if let Some(size) = response.audio_output_size {
+            attributes.push(KeyValue::new(AUDIO_OUTPUT_SIZE, size as i64));
+        }
+        if let Some(time) = response.time_to_first_token {
+            attributes.push(KeyValue::new(
+                OPENAI_STREAM_TIME_TO_FIRST_TOKEN_MS,
+                milliseconds(time),
+            ));
+        }
+        if let Some(chunks) = response.output_chunks {
+            attributes.push(KeyValue::new(OPENAI_STREAM_OUTPUT_CHUNKS, chunks as i64));
+        }
+        if let Some(latency) = response.inter_token_latency {
+            for (key, gap) in [
+                (OPENAI_STREAM_INTER_TOKEN_LATENCY_MEAN_MS, latency.mean),
+                (OPENAI_STREAM_INTER_TOKEN_LATENCY_MIN_MS, latency.min),
+                (OPENAI_STREAM_INTER_TOKEN_LATENCY_MAX_MS, latency.max),
+            ] {
+                attributes.push(KeyValue::new(key, milliseconds(gap)));
+            }
+        }
+        if let Some(attempts) = response.attempts {
+            attributes.push(KeyValue::new(OPENAI_REQUEST_ATTEMPTS, attempts as i64));
+        }
+        if let Some(duration) = response.duration {
+            attributes.push(KeyValue::new(DURATION_MS, duration.as_millis() as i64));
+        }
+        attributes
+    }
+
+    fn error_attributes(&self, error: &ErrorRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        for (key, value) in [
+            (OPENAI_ERROR_CODE, error.code),
+            (OPENAI_ERROR_TYPE, error.kind),
+            (OPENAI_ERROR_PARAM, error.param),
+        ] {
+            if let Some(value) = value {
+                attributes.push(KeyValue::new(key, value.to_string()));
+            }
+        }
+        if let Some(results) = error.content_filter {
+            attributes.extend(content_filter_attributes(results));
+        }
+        if error.cancelled {
+            attributes.push(KeyValue::new(OPENAI_STREAM_CANCELLED, true));
+        }
+        attributes
+    }
+}
+
+/// A duration in (fractional) milliseconds
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// The [OpenInference](https://github.com/Arize-ai/openinference/blob/main/spec/semantic_conventions.md)
+/// attributes read by Arize Phoenix: span kind, model, invocation parameters, input and
+/// output values, chat messages and token counts
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpenInferenceConvention;
+
+impl AttributeConvention for OpenInferenceConvention {
+    fn trace_attributes(&self, trace: &TraceRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        for (key, value) in [
+            ("session.id", trace.session_id()),
+            ("user.id", trace.user_id()),
+        ] {
+            if let Some(value) = value {
+                attributes.push(KeyValue::new(key, value.into_owned()));
+            }
+        }
+        let tags: Vec<StringValue> = trace.tags().into_iter().map(StringValue::from).collect();
+        if !tags.is_empty() {
+            attributes.push(KeyValue::new(
+                "tag.tags",
+                opentelemetry::Value::Array(tags.into()),
+            ));
+        }
+        if let Some(metadata) = trace.metadata() {
+            attributes.push(KeyValue::new("metadata", metadata.to_string()));
+        }
+        attributes
+    }
+
+    fn request_attributes(&self, request: &RequestRecord<'_>) -> Vec<KeyValue> {
+        let span_kind = match request.operation {
+            "embedding" => "EMBEDDING",
+            _ => "LLM",
+        };
+        let mut attributes = vec![
+            KeyValue::new("openinference.span.kind", span_kind),
+            KeyValue::new("llm.system", "openai"),
+            KeyValue::new("llm.provider", "openai"),
+        ];
+        if let Some(model) = request.model {
+            let key = match request.operation {
+                "embedding" => "embedding.model_name",
+                _ => "llm.model_name",
+            };
+            attributes.push(KeyValue::new(key, model.to_string()));
+        }
+        if !request.parameters.is_empty() {
+            attributes.push(KeyValue::new(
+                "llm.invocation_parameters",
+                Value::Object(request.parameters.clone()).to_string(),
+            ));
+        }
+        if let Some(input) = request.input {
+            attributes.push(KeyValue::new("input.value", input.to_string()));
+            attributes.push(KeyValue::new("input.mime_type", "application/json"));
+            if let Some(messages) = input.get("messages").and_then(Value::as_array) {
+                openinference_messages("llm.input_messages", messages.iter(), &mut attributes);
+            }
+        }
+        attributes
+    }
+
+    fn response_attributes(&self, response: &ResponseRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        if let Some(model) = response.model.filter(|_| response.operation != "embedding") {
+            attributes.push(KeyValue::new("llm.model_name", model.to_string()));
+        }
+        if let Some(output) = response.output {
+            attributes.push(KeyValue::new("output.value", output.to_string()));
+            attributes.push(KeyValue::new("output.mime_type", "application/json"));
+            if let Some(choices) = output.get("choices").and_then(Value::as_array) {
+                let messages = choices.iter().filter_map(|choice| choice.get("message"));
+                openinference_messages("llm.output_messages", messages, &mut attributes);
+            }
+        }
+        for (key, count) in [
+            ("llm.token_count.prompt", response.input_tokens()),
+            ("llm.token_count.completion", response.output_tokens()),
+            ("llm.token_count.total", response.total_tokens()),
+            (
+                "llm.token_count.prompt_details.cache_read",
+                response.detail("input_cached_tokens"),
+            ),
+            (
+                "llm.token_count.completion_details.reasoning",
+                response.detail("output_reasoning_tokens"),
+            ),
+        ] {
+            if let Some(count) = count {
+                attributes.push(KeyValue::new(key, count));
+            }
+        }
+        if let Some(cost) = response.total_cost() {
+            attributes.push(KeyValue::new("llm.cost.total", cost));
+        }
+        attributes
+    }
+}
+
+/// Flatten chat messages into `<prefix>.<i>.message.*` attributes
+fn openinference_messages<'a>(
+    prefix: &str,
+    messages: impl Iterator<Item = &'a Value>,
+    attributes: &mut Vec<KeyValue>,
+) {
+    for (i, message) in messages.enumerate() {
+        let prefix = format!("{}.{}.message", prefix, i);
+        for field in ["role", "name", "tool_call_id"] {
+            if let Some(value) = message.get(field).and_then(Value::as_str) {
+                attributes.push(KeyValue::new(
+                    format!("{}.{}", prefix, field),
+                    value.to_string(),
+                ));
+            }
+        }
+        match message.get("content") {
+            Some(Value::String(content)) => {
+                attributes.push(KeyValue::new(
+                    format!("{}.content", prefix),
+                    content.clone(),
+                ));
+            }
+            Some(Value::Array(parts)) => {
+                for (j, part) in parts.iter().enumerate() {
+                    let prefix = format!("{}.contents.{}.message_content", prefix, j);
+                    openinference_content_part(&prefix, part, attributes);
+                }
+            }
+            _ => {}
+        }
+        for (j, tool_call) in tool_calls(message).enumerate() {
+            let prefix = format!("{}.tool_calls.{}.tool_call", prefix, j);
+            if let Some(id) = tool_call.id {
+                attributes.push(KeyValue::new(format!("{}.id", prefix), id.to_string()));
+            }
+            if let Some(name) = tool_call.name {
+                attributes.push(KeyValue::new(
+                    format!("{}.function.name", prefix),
+                    name.to_string(),
+                ));
+            }
+            if let Some(arguments) = tool_call.arguments {
+                attributes.push(KeyValue::new(
+                    format!("{}.function.arguments", prefix),
+                    arguments,
+                ));
+            }
+        }
+    }
+}
+
+fn openinference_content_part(prefix: &str, part: &Value, attributes: &mut Vec<KeyValue>) {
+    match part.get("type").and_then(Value::as_str) {
+        Some("text") => {
+            attributes.push(KeyValue::new(format!("{}.type", prefix), "text"));
+            if let Some(text) = part.get("text").and_then(Value::as_str) {
+                attributes.push(KeyValue::new(format!("{}.text", prefix), text.to_string()));
+            }
+        }
+        Some("image_url") => {
+            attributes.push(KeyValue::new(format!("{}.type", prefix), "image"));
+            // Inline images are recorded as placeholders rather than URLs
+            if let Some(url) = part.pointer("/image_url/url").and_then(Value::as_str) {
+                attributes.push(KeyValue::new(
+                    format!("{}.image.image.url", prefix),
+                    url.to_string(),
+                ));
+            }
+        }
+        Some(other) => {
+            attributes.push(KeyValue::new(format!("{}.type", prefix), other.to_string()));
+        }
+        None => {}
+    }
+}
+
+/// The [OpenLLMetry](https://github.com/traceloop/openllmetry) attributes read by
+/// Traceloop: request type, model, parameters, indexed prompts and completions and token
+/// counts
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpenLlmetryConvention;
+
+impl AttributeConvention for OpenLlmetryConvention {
+    fn trace_attributes(&self, trace: &TraceRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        for (key, value) in [
+            ("user_id", trace.user_id()),
+            ("session_id", trace.session_id()),
+        ] {
+            if let Some(value) = value {
+                attributes.push(KeyValue::new(
+                    format!("traceloop.association.properties.{}", key),
+                    value.into_owned(),
+                ));
+            }
+        }
+        attributes
+    }
+
+    fn request_attributes(&self, request: &RequestRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = vec![
+            KeyValue::new("llm.request.type", request.operation.to_string()),
+            KeyValue::new(GEN_AI_SYSTEM, "openai"),
+        ];
+        if let Some(model) = request.model {
+            attributes.push(KeyValue::new(GEN_AI_REQUEST_MODEL, model.to_string()));
+        }
+        attributes.extend(parameter_attributes(request.parameters));
+
+        let Some(input) = request.input else {
+            return attributes;
+        };
+        if let Some(messages) = input.get("messages").and_then(Value::as_array) {
+            for (i, message) in messages.iter().enumerate() {
+                openllmetry_message(&format!("gen_ai.prompt.{}", i), message, &mut attributes);
+            }
+        } else if let Some(prompts) = input.get("prompt").or_else(|| input.get("input")) {
+            // Text completions and embeddings: one prompt per input string
+            let prompts = match prompts {
+                Value::Array(prompts) => prompts.iter().collect(),
+                prompt => vec![prompt],
+            };
+            for (i, prompt) in prompts.into_iter().enumerate() {
+                if let Some(content) = json_attribute_value(prompt) {
+                    attributes.push(KeyValue::new(format!("gen_ai.prompt.{}.role", i), "user"));
+                    attributes.push(KeyValue::new(
+                        format!("gen_ai.prompt.{}.content", i),
+                        content,
+                    ));
+                }
+            }
+        }
+        attributes
+    }
+
+    fn response_attributes(&self, response: &ResponseRecord<'_>) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        if let Some(model) = response.model {
+            attributes.push(KeyValue::new(GEN_AI_RESPONSE_MODEL, model.to_string()));
+        }
+        if let Some(choices) = response
+            .output
+            .and_then(|output| output.get("choices"))
+            .and_then(Value::as_array)
+        {
+            for (i, choice) in choices.iter().enumerate() {
+                let prefix = format!("gen_ai.completion.{}", i);
+                match choice.get("message") {
+                    Some(message) => openllmetry_message(&prefix, message, &mut attributes),
+                    // Text completions record the text of each choice
+                    None => {
+                        if let Some(content) = json_attribute_value(choice) {
+                            attributes.push(KeyValue::new(format!("{}.content", prefix), content));
+                        }
+                    }
+                }
+                if let Some(reason) = choice.get("finish_reason").and_then(Value::as_str) {
+                    attributes.push(KeyValue::new(
+                        format!("{}.finish_reason", prefix),
+                        reason.to_string(),
+                    ));
+                }
+            }
+        }
+        for (key, count) in [
+            ("gen_ai.usage.prompt_tokens", response.input_tokens()),
+            ("gen_ai.usage.completion_tokens", response.output_tokens()),
+            ("llm.usage.total_tokens", response.total_tokens()),
+            (
+                "gen_ai.usage.cache_read_input_tokens",
+                response.detail("input_cached_tokens"),
+            ),
+        ] {
+            if let Some(count) = count {
+                attributes.push(KeyValue::new(key, count));
+            }
+        }
+        attributes
+    }
+}
+
+/// Flatten a chat message into `<prefix>.*` attributes
+fn openllmetry_message(prefix: &str, message: &Value, attributes: &mut Vec<KeyValue>) {
+    for field in ["role", "name", "tool_call_id"] {
+        if let Some(value) = message.get(field).and_then(Value::as_str) {
+            attributes.push(KeyValue::new(
+                format!("{}.{}", prefix, field),
+                value.to_string(),
+            ));
+        }
+    }
+    if let Some(content) = message.get("content").and_then(json_attribute_value) {
+        attributes.push(KeyValue::new(format!("{}.content", prefix), content));
+    }
+    for (j, tool_call) in tool_calls(message).enumerate() {
+        let prefix = format!("{}.tool_calls.{}", prefix, j);
+        if let Some(id) = tool_call.id {
+            attributes.push(KeyValue::new(format!("{}.id", prefix), id.to_string()));
+        }
+        if let Some(name) = tool_call.name {
+            attributes.push(KeyValue::new(format!("{}.name", prefix), name.to_string()));
+        }
+        if let Some(arguments) = tool_call.arguments {
+            attributes.push(KeyValue::new(format!("{}.arguments", prefix), arguments));
+        }
+    }
+}
+
+/// A string as is, anything else but null as JSON
+fn json_attribute_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+struct ToolCall<'a> {
+    id: Option<&'a str>,
+    name: Option<&'a str>,
+    arguments: Option<String>,
+}
+
+/// The tool calls of a message, either as sent (`function.name`, `function.arguments`) or
+/// as recorded for choices (`name`, parsed `arguments`)
+fn tool_calls(message: &Value) -> impl Iterator<Item = ToolCall<'_>> {
+    message
+        .get("tool_calls")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|tool_call| {
+            let function = tool_call.get("function").unwrap_or(tool_call);
+            ToolCall {
+                id: tool_call.get("id").and_then(Value::as_str),
+                name: function.get("name").and_then(Value::as_str),
+                arguments: function.get("arguments").and_then(json_attribute_value),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn attribute(attributes: &[KeyValue], key: &str) -> Option<String> {
+        attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.as_str().into_owned())
+    }
+
+    fn chat_input() -> Value {
+        json!({"messages": [
+            {"role": "system", "content": "Be brief"},
+            {"role": "user", "content": [
+                {"type": "text", "text": "What is this?"},
+                {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+            ]},
+            {"role": "assistant", "tool_calls": [
+                {"id": "call_1", "type": "function", "function": {"name": "lookup", "arguments": "{\"q\":\"cat\"}"}}
+            ]},
+            {"role": "tool", "tool_call_id": "call_1", "content": "A cat"}
+        ]})
+    }
+
+    fn chat_output() -> (Value, Vec<String>) {
+        let output = json!({"choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": "A cat."},
+            "finish_reason": "stop"
+        }]});
+        (output, vec!["stop".to_string()])
+    }
+
+    #[test]
+    fn test_openinference_attributes() {
+        let parameters = Map::from_iter([("temperature".to_string(), json!(0.2))]);
+        let input = chat_input();
+        let request = RequestRecord {
+            model: Some("gpt-4o"),
+            input: Some(&input),
+            ..RequestRecord::new("chat", &parameters)
+        };
+        let attributes = OpenInferenceConvention.request_attributes(&request);
+
+        assert_eq!(
+            attribute(&attributes, "openinference.span.kind").as_deref(),
+            Some("LLM")
+        );
+        assert_eq!(
+            attribute(&attributes, "llm.model_name").as_deref(),
+            Some("gpt-4o")
+        );
+        assert_eq!(
+            attribute(&attributes, "llm.invocation_parameters").as_deref(),
+            Some("{\"temperature\":0.2}")
+        );
+        assert_eq!(
+            attribute(&attributes, "llm.input_messages.0.message.content").as_deref(),
+            Some("Be brief")
+        );
+        assert_eq!(
+            attribute(
+                &attributes,
+                "llm.input_messages.1.message.contents.1.message_content.image.image.url"
+            )
+            .as_deref(),
+            Some("https://example.com/cat.png")
+        );
+        assert_eq!(
+            attribute(
+                &attributes,
+                "llm.input_messages.2.message.tool_calls.0.tool_call.function.arguments"
+            )
+            .as_deref(),
+            Some("{\"q\":\"cat\"}")
+        );
+        assert_eq!(
+            attribute(&attributes, "llm.input_messages.3.message.tool_call_id").as_deref(),
+            Some("call_1")
+        );
+
+        let usage = Usage::parse(&json!({
+            "prompt_tokens": 20,
+            "completion_tokens": 3,
+            "total_tokens": 23,
+            "prompt_tokens_details": {"cached_tokens": 8}
+        }));
+        let (output, finish_reasons) = chat_output();
+        let response = ResponseRecord {
+            model: Some("gpt-4o-2024-08-06"),
+            finish_reasons: &finish_reasons,
+            output: Some(&output),
+            usage: Some(&usage),
+            ..ResponseRecord::new("chat")
+        };
+        let attributes = OpenInferenceConvention.response_attributes(&response);
+        assert_eq!(
+            attribute(&attributes, "llm.output_messages.0.message.content").as_deref(),
+            Some("A cat.")
+        );
+        assert_eq!(
+            attribute(&attributes, "llm.token_count.prompt").as_deref(),
+            Some("20")
+        );
+        assert_eq!(
+            attribute(&attributes, "llm.token_count.prompt_details.cache_read").as_deref(),
+            Some("8")
+        );
+    }
+
+    #[test]
+    fn test_openllmetry_attributes() {
+        let parameters = Map::new();
+        let input = chat_input();
+        let request = RequestRecord {
+            model: Some("gpt-4o"),
+            input: Some(&input),
+            ..RequestRecord::new("chat", &parameters)
+        };
+        let attributes = OpenLlmetryConvention.request_attributes(&request);
+
+        assert_eq!(
+            attribute(&attributes, "llm.request.type").as_deref(),
+            Some("chat")
+        );
+        assert_eq!(
+            attribute(&attributes, "gen_ai.prompt.0.role").as_deref(),
+            Some("system")
+        );
+        assert!(attribute(&attributes, "gen_ai.prompt.1.content")
+            .unwrap()
+            .contains("What is this?"));
+        assert_eq!(
+            attribute(&attributes, "gen_ai.prompt.2.tool_calls.0.name").as_deref(),
+            Some("lookup")
+        );
+
+        let usage = Usage::parse(&json!({"prompt_tokens": 20, "completion_tokens": 3}));
+        let (output, finish_reasons) = chat_output();
+        let response = ResponseRecord {
+            finish_reasons: &finish_reasons,
+            output: Some(&output),
+            usage: Some(&usage),
+            ..ResponseRecord::new("chat")
+        };
+        let attributes = OpenLlmetryConvention.response_attributes(&response);
+        assert_eq!(
+            attribute(&attributes, "gen_ai.completion.0.content").as_deref(),
+            Some("A cat.")
+        );
+        assert_eq!(
+            attribute(&attributes, "gen_ai.completion.0.finish_reason").as_deref(),
+            Some("stop")
+        );
+        assert_eq!(
+            attribute(&attributes, "gen_ai.usage.completion_tokens").as_deref(),
+            Some("3")
+        );
+    }
+
+    #[test]
+    fn test_langfuse_error_level() {
+        let error = ErrorRecord {
+            cancelled: true,
+            ..ErrorRecord::new(
+                "chat",
+                "Response stream dropped before completion",
+                "cancelled",
+            )
+        };
+        let attributes = LangfuseConvention.error_attributes(&error);
+        assert_eq!(
+            attribute(&attributes, LangfuseAttributes::OBSERVATION_LEVEL).as_deref(),
+            Some("WARNING")
+        );
+        assert_eq!(
+            attribute(&attributes, LangfuseAttributes::OBSERVATION_STATUS_MESSAGE).as_deref(),
+            Some("Response stream dropped before completion")
+        );
+
+        let error = ErrorRecord {
+            cancelled: false,
+            ..error
+        };
+        assert_eq!(
+            attribute(
+                &LangfuseConvention.error_attributes(&error),
+                LangfuseAttributes::OBSERVATION_LEVEL
+            )
+            .as_deref(),
+            Some("ERROR")
+        );
+        // Conventions without error attributes record none
+        assert!(OpenInferenceConvention.error_attributes(&error).is_empty());
+    }
+
+    #[test]
+    fn test_openai_details() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", "req-1".parse().unwrap());
+        let response = ResponseRecord {
+            headers: Some(&headers),
+            system_fingerprint: Some("fp_1"),
+            unpriced_model: Some("o1-pro"),
+            inter_token_latency: Some(InterTokenLatency {
+                mean: Duration::from_millis(20),
+                min: Duration::from_millis(10),
+                max: Duration::from_millis(30),
+            }),
+            duration: Some(Duration::from_millis(1500)),
+            ..ResponseRecord::new("chat")
+        };
+        let attributes = OpenAIConvention.response_attributes(&response);
+        for (key, value) in [
+            ("http.response.header.x-request-id", "[\"req-1\"]"),
+            (OPENAI_RESPONSE_SYSTEM_FINGERPRINT, "fp_1"),
+            (OPENAI_PRICING_UNKNOWN_MODEL, "o1-pro"),
+            (OPENAI_STREAM_INTER_TOKEN_LATENCY_MAX_MS, "30"),
+            (DURATION_MS, "1500"),
+        ] {
+            assert_eq!(
+                attribute(&attributes, key).as_deref(),
+                Some(value),
+                "{}",
+                key
+            );
+        }
+        // The other conventions have no names for these details
+        assert!(OpenInferenceConvention
+            .response_attributes(&response)
+            .is_empty());
+
+        let error = ErrorRecord {
+            code: Some("rate_limit_exceeded"),
+            cancelled: true,
+            ..ErrorRecord::new("chat", "HTTP 429", "rate_limit_exceeded")
+        };
+        let attributes = OpenAIConvention.error_attributes(&error);
+        assert_eq!(
+            attribute(&attributes, OPENAI_ERROR_CODE).as_deref(),
+            Some("rate_limit_exceeded")
+        );
+        assert_eq!(
+            attribute(&attributes, OPENAI_STREAM_CANCELLED).as_deref(),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn test_trace_attributes() {
+        let attributes = [
+            KeyValue::new(LangfuseAttributes::TRACE_NAME, "checkout"),
+            KeyValue::new(LangfuseAttributes::TRACE_SESSION_ID, "session-1"),
+            KeyValue::new(LangfuseAttributes::TRACE_TAGS, "[\"eval\",\"beta\"]"),
+            KeyValue::new(LangfuseAttributes::TRACE_METADATA, "{\"tenant\":\"acme\"}"),
+            KeyValue::new("deployment.environment", "staging"),
+        ];
+        let trace = TraceRecord {
+            attributes: &attributes,
+        };
+        assert_eq!(trace.tags(), ["eval", "beta"]);
+
+        let langfuse = LangfuseConvention.trace_attributes(&trace);
+        assert_eq!(langfuse.len(), 4);
+        let openinference = OpenInferenceConvention.trace_attributes(&trace);
+        assert_eq!(
+            attribute(&openinference, "session.id").as_deref(),
+            Some("session-1")
+        );
+        assert_eq!(
+            attribute(&openinference, "metadata").as_deref(),
+            Some("{\"tenant\":\"acme\"}")
+        );
+        assert!(attribute(&openinference, LangfuseAttributes::TRACE_NAME).is_none());
+        // Attributes under names of their own are left to the configuration to record
+        assert_eq!(
+            trace.custom_attributes(),
+            [KeyValue::new("deployment.environment", "staging")]
+        );
+    }
+
+    #[test]
+    fn test_openllmetry_embedding_prompts() {
+        let parameters = Map::new();
+        let input = json!({"input": ["first", "second"]});
+        let request = RequestRecord {
+            input: Some(&input),
+            ..RequestRecord::new("embedding", &parameters)
+        };
+        let attributes = OpenLlmetryConvention.request_attributes(&request);
+        assert_eq!(
+            attribute(&attributes, "gen_ai.prompt.1.content").as_deref(),
+            Some("second")
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Recording of OpenAI error responses
+//!
+//! Error responses carry `{"error": {"message", "type", "code", "param"}}`. Azure OpenAI
+//! adds the results of its content filter under `innererror.content_filter_result` when a
+//! prompt is rejected, which the [`OpenAIConvention`](crate::OpenAIConvention) records per
+//! category as `azure.content_filter.<category>.{filtered,severity,detected}`.
+
+use crate::conventions::ErrorRecord;
+use http::StatusCode;
+use opentelemetry::KeyValue;
+use serde_json::Value;
+
+/// The `code` of an OpenAI error, e.g. `context_length_exceeded`
+pub(crate) const OPENAI_ERROR_CODE: &str = "openai.error.code";
+/// The `type` of an OpenAI error, e.g. `invalid_request_error`
+pub(crate) const OPENAI_ERROR_TYPE: &str = "openai.error.type";
+/// The request parameter an OpenAI error relates to, e.g. `messages`
+pub(crate) const OPENAI_ERROR_PARAM: &str = "openai.error.param";
+
+/// A parsed error response
+pub(crate) struct ErrorResponse {
+    /// Human readable description, used as the span status message
+    pub(crate) message: String,
+    /// The low-cardinality `error.type`
+    pub(crate) error_type: String,
+    pub(crate) code: Option<String>,
+    pub(crate) kind: Option<String>,
+    pub(crate) param: Option<String>,
+    /// Azure content filter results, per category
+    pub(crate) content_filter: Option<Value>,
+}
+
+impl ErrorResponse {
+    /// Parse the body of a non-success response; bodies that are not OpenAI errors are
+    /// described by their status alone
+    pub(crate) fn parse(status: StatusCode, body: &[u8]) -> Self {
+        let error = serde_json::from_slice::<Value>(body)
+            .ok()
+            .and_then(|json| json.get("error").cloned());
+        let field = |key: &str| {
+            error
+                .as_ref()
+                .and_then(|e| e.get(key))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+
+        let message = match field("message") {
+            Some(message) => format!("HTTP {}: {}", status, message),
+            None => format!("HTTP {}", status),
+        };
+        let code = field("code");
+        let kind = field("type");
+        let content_filter = error
+            .as_ref()
+            .and_then(|e| e.pointer("/innererror/content_filter_result"))
+            .cloned();
+
+        // error.type should have low cardinality: prefer the error code, then its type
+        let error_type = code
+            .clone()
+            .or_else(|| kind.clone())
+            .unwrap_or_else(|| status.as_u16().to_string());
+        Self {
+            message,
+            error_type,
+            code,
+            kind,
+            param: field("param"),
+            content_filter,
+        }
+    }
+
+    /// The error as recorded by the attribute conventions
+    pub(crate) fn record<'a>(&'a self, operation: &'a str) -> ErrorRecord<'a> {
+        ErrorRecord {
+            code: self.code.as_deref(),
+            kind: self.kind.as_deref(),
+            param: self.param.as_deref(),
+            content_filter: self.content_filter.as_ref(),
+            ..ErrorRecord::new(operation, &self.message, &self.error_type)
+        }
+    }
+}
+
+/// The `code` of an OpenAI error body, if it is one
+pub(crate) fn error_code(body: &[u8]) -> Option<String> {
+    serde_json::from_slice::<Value>(body)
+        .ok()?
+        .pointer("/error/code")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Structured attributes of Azure content filter results, per category
+pub(crate) fn content_filter_attributes(results: &Value) -> Vec<KeyValue> {
+    let Some(categories) = results.as_object() else {
+        return Vec::new();
+    };
+    let mut attributes = Vec::new();
+    for (category, result) in categories {
+        let prefix = format!("azure.content_filter.{}", category);
+        for key in ["filtered", "detected"] {
+            if let Some(value) = result.get(key).and_then(|v| v.as_bool()) {
+                attributes.push(KeyValue::new(format!("{}.{}", prefix, key), value));
+            }
+        }
+        if let Some(severity) = result.get("severity").and_then(|v| v.as_str()) {
+            attributes.push(KeyValue::new(
+                format!("{}.severity", prefix),
+                severity.to_string(),
+            ));
+        }
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_azure_content_filter_error() {
+        let body = json!({"error": {
+            "message": "The response was filtered due to the prompt triggering Azure OpenAI's content management policy.",
+            "type": null,
+            "param": "prompt",
+            "code": "content_filter",
+            "status": 400,
+            "innererror": {
+                "code": "ResponsibleAIPolicyViolation",
+                "content_filter_result": {
+                    "hate": {"filtered": false, "severity": "safe"},
+                    "violence": {"filtered": true, "severity": "high"},
+                    "jailbreak": {"filtered": false, "detected": false}
+                }
+            }
+        }});
+
+        let error = ErrorResponse::parse(StatusCode::BAD_REQUEST, body.to_string().as_bytes());
+        assert!(error
+            .message
+            .starts_with("HTTP 400 Bad Request: The response was filtered"));
+        assert_eq!(error.error_type, "content_filter");
+        assert_eq!(error.param.as_deref(), Some("prompt"));
+        let attributes = content_filter_attributes(error.content_filter.as_ref().unwrap());
+        for expected in [
+            KeyValue::new("azure.content_filter.violence.filtered", true),
+            KeyValue::new("azure.content_filter.violence.severity", "high"),
+            KeyValue::new("azure.content_filter.jailbreak.detected", false),
+        ] {
+            assert!(attributes.contains(&expected), "{:?}", expected);
+        }
+    }
+
+    #[test]
+    fn test_non_json_error_body() {
+        let error = ErrorResponse::parse(StatusCode::BAD_GATEWAY, b"<html>Bad gateway</html>");
+        assert_eq!(error.message, "HTTP 502 Bad Gateway");
+        assert_eq!(error.error_type, "502");
+    }
+}
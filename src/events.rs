@@ -0,0 +1,289 @@
+//! GenAI message events
+//!
+//! Besides the Langfuse input and output attributes, the GenAI semantic conventions
+//! describe a conversation as events: one per request message (`gen_ai.system.message`,
+//! `gen_ai.user.message`, `gen_ai.assistant.message`, `gen_ai.tool.message`) and one per
+//! response choice (`gen_ai.choice`). With
+//! [`with_message_events`](crate::OpenAITracingMiddlewareBuilder::with_message_events),
+//! chat completion spans carry these events, so backends such as Jaeger, Tempo and Datadog
+//! render the conversation natively.
+//!
+//! The fields of an event's body (`content`, `tool_calls`, `message`, ...) are recorded as
+//! attributes of the span event. Message content goes through the same capture mode,
+//! redactors and size limits as the observation input and output, truncated fields being
+//! marked with `<field>.truncated` and `<field>.original_size`; the structure of the
+//! conversation (choice index, finish reason, tool call ID) is always recorded as is.
+
+use crate::config::OpenAITracingConfig;
+use crate::redaction::ContentKind;
+use crate::truncation::{serialize_within, truncate_str};
+use opentelemetry::KeyValue;
+use opentelemetry_semantic_conventions::attribute::GEN_AI_SYSTEM;
+use serde_json::{Map, Value};
+
+/// A system (or developer) instruction
+pub(crate) const GEN_AI_SYSTEM_MESSAGE: &str = "gen_ai.system.message";
+/// A message of the user
+pub(crate) const GEN_AI_USER_MESSAGE: &str = "gen_ai.user.message";
+/// A previous message of the model, possibly with tool calls
+pub(crate) const GEN_AI_ASSISTANT_MESSAGE: &str = "gen_ai.assistant.message";
+/// The result of a tool call
+pub(crate) const GEN_AI_TOOL_MESSAGE: &str = "gen_ai.tool.message";
+/// A choice of the response
+pub(crate) const GEN_AI_CHOICE: &str = "gen_ai.choice";
+
+/// Name and attributes of the event of each message of a chat completion request
+pub(crate) fn message_events(
+    config: &OpenAITracingConfig,
+    request: &Value,
+) -> Vec<(&'static str, Vec<KeyValue>)> {
+    let Some(messages) = request.get("messages").and_then(Value::as_array) else {
+        return Vec::new();
+    };
+    messages
+        .iter()
+        .filter_map(|message| {
+            let role = message.get("role")?.as_str()?;
+            let name = match role {
+                "system" | "developer" => GEN_AI_SYSTEM_MESSAGE,
+                "user" => GEN_AI_USER_MESSAGE,
+                "assistant" => GEN_AI_ASSISTANT_MESSAGE,
+                "tool" | "function" => GEN_AI_TOOL_MESSAGE,
+                _ => return None,
+            };
+
+            let mut structure = Map::new();
+            // The role is only recorded when the event name doesn't imply it
+            if !matches!(role, "system" | "user" | "assistant" | "tool") {
+                structure.insert("role".to_string(), Value::from(role));
+            }
+            if let Some(id) = message.get("tool_call_id") {
+                structure.insert("id".to_string(), id.clone());
+            }
+            let mut body = Map::new();
+            for field in ["content", "tool_calls"] {
+                if let Some(value) = message.get(field).filter(|v| !v.is_null()) {
+                    body.insert(field.to_string(), value.clone());
+                }
+            }
+            Some((
+                name,
+                event_attributes(config, ContentKind::Input, structure, body),
+            ))
+        })
+        .collect()
+}
+
+/// Attributes of the `gen_ai.choice` event of each choice of a chat completion response
+pub(crate) fn choice_events(config: &OpenAITracingConfig, response: &Value) -> Vec<Vec<KeyValue>> {
+    let Some(choices) = response.get("choices").and_then(Value::as_array) else {
+        return Vec::new();
+    };
+    choices
+        .iter()
+        .map(|choice| {
+            let mut structure = Map::new();
+            for field in ["index", "finish_reason"] {
+                if let Some(value) = choice.get(field) {
+                    structure.insert(field.to_string(), value.clone());
+                }
+            }
+            let mut body = Map::new();
+            if let Some(fields) = choice.get("message").and_then(Value::as_object) {
+                let message: Map<String, Value> = fields
+                    .iter()
+                    .filter(|(key, value)| match key.as_str() {
+                        "role" => value.as_str() != Some("assistant"),
+                        _ => !value.is_null(),
+                    })
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                body.insert("message".to_string(), Value::Object(message));
+            }
+            event_attributes(config, ContentKind::Output, structure, body)
+        })
+        .collect()
+}
+
+/// The attributes of an event: its structure as is, and its body as content is recorded
+fn event_attributes(
+    config: &OpenAITracingConfig,
+    kind: ContentKind,
+    structure: Map<String, Value>,
+    body: Map<String, Value>,
+) -> Vec<KeyValue> {
+    let body = match body.is_empty() {
+        true => None,
+        false => config.record_content(kind, Value::Object(body)),
+    };
+    let body = match body {
+        Some(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+
+    let mut attributes = vec![KeyValue::new(GEN_AI_SYSTEM, "openai")];
+    for (key, value) in structure {
+        if let Some(value) = attribute_value(value, None) {
+            attributes.push(KeyValue::new(key, value.0));
+        }
+    }
+    let limit = config.content_limit(kind);
+    for (key, value) in body {
+        let Some((value, original_size)) = attribute_value(value, limit) else {
+            continue;
+        };
+        attributes.push(KeyValue::new(key.clone(), value));
+        if let Some(size) = original_size {
+            attributes.push(KeyValue::new(format!("{}.truncated", key), true));
+            attributes.push(KeyValue::new(format!("{}.original_size", key), size as i64));
+        }
+    }
+    attributes
+}
+
+/// A field as attribute value within `limit` bytes, with its original size if it was
+/// truncated; strings as is, anything else but null as JSON
+fn attribute_value(
+    value: Value,
+    limit: Option<usize>,
+) -> Option<(opentelemetry::Value, Option<usize>)> {
+    let value = match value {
+        Value::Null => return None,
+        Value::Bool(b) => (b.into(), None),
+        Value::Number(n) if n.is_i64() => (n.as_i64().unwrap_or_default().into(), None),
+        Value::String(s) => match limit.filter(|limit| s.len() > *limit) {
+            Some(limit) => (truncate_str(&s, limit).into_owned().into(), Some(s.len())),
+            None => (s.into(), None),
+        },
+        value => {
+            let json = value.to_string();
+            match limit.filter(|limit| json.len() > *limit) {
+                Some(limit) => (serialize_within(&value, limit).0.into(), Some(json.len())),
+                None => (json.into(), None),
+            }
+        }
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ContentCapture, OpenAITracingMiddlewareBuilder};
+    use serde_json::json;
+
+    fn config(capture: ContentCapture) -> OpenAITracingConfig {
+        OpenAITracingMiddlewareBuilder::new()
+            .with_content_capture(capture)
+            .config()
+            .clone()
+    }
+
+    fn attribute<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a opentelemetry::Value> {
+        attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| &kv.value)
+    }
+
+    #[test]
+    fn test_message_events() {
+        let request = json!({"model": "gpt-4o", "messages": [
+            {"role": "developer", "content": "Be brief"},
+            {"role": "user", "content": "Weather in Paris?"},
+            {"role": "assistant", "content": null, "tool_calls": [
+                {"id": "call_1", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}}
+            ]},
+            {"role": "tool", "tool_call_id": "call_1", "content": "Sunny"}
+        ]});
+
+        let events = message_events(&config(ContentCapture::Full), &request);
+        let names: Vec<_> = events.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                GEN_AI_SYSTEM_MESSAGE,
+                GEN_AI_USER_MESSAGE,
+                GEN_AI_ASSISTANT_MESSAGE,
+                GEN_AI_TOOL_MESSAGE
+            ]
+        );
+        assert_eq!(
+            events[0].1,
+            vec![
+                KeyValue::new(GEN_AI_SYSTEM, "openai"),
+                KeyValue::new("role", "developer"),
+                KeyValue::new("content", "Be brief"),
+            ]
+        );
+        assert_eq!(attribute(&events[1].1, "role"), None);
+        assert_eq!(attribute(&events[2].1, "content"), None);
+        let tool_calls = attribute(&events[2].1, "tool_calls").unwrap().as_str();
+        assert!(tool_calls.contains("\"name\":\"weather\""));
+        assert_eq!(
+            attribute(&events[3].1, "id"),
+            Some(&opentelemetry::Value::from("call_1"))
+        );
+    }
+
+    #[test]
+    fn test_choice_events_without_content() {
+        let response = json!({"choices": [
+            {"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": "Sunny"}}
+        ]});
+
+        let events = choice_events(&config(ContentCapture::Full), &response);
+        assert_eq!(
+            events,
+            vec![vec![
+                KeyValue::new(GEN_AI_SYSTEM, "openai"),
+                KeyValue::new("index", 0),
+                KeyValue::new("finish_reason", "stop"),
+                KeyValue::new("message", "{\"content\":\"Sunny\"}"),
+            ]]
+        );
+
+        // The structure of the conversation is recorded even without content
+        let events = choice_events(&config(ContentCapture::Off), &response);
+        assert_eq!(
+            events,
+            vec![vec![
+                KeyValue::new(GEN_AI_SYSTEM, "openai"),
+                KeyValue::new("index", 0),
+                KeyValue::new("finish_reason", "stop"),
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_event_content_limit() {
+        let config = OpenAITracingMiddlewareBuilder::new()
+            .with_attribute_limit(crate::LangfuseAttributes::OBSERVATION_OUTPUT, 40)
+            .config()
+            .clone();
+        let content = "Sunny with a chance of rain later in the afternoon";
+        let response = json!({"choices": [
+            {"index": 0, "finish_reason": "stop", "message": {"role": "assistant", "content": content}}
+        ]});
+
+        let events = choice_events(&config, &response);
+        let message = attribute(&events[0], "message").unwrap().as_str();
+        assert!(message.len() <= 40, "{}", message);
+        assert!(serde_json::from_str::<Value>(&message).is_ok());
+        assert_eq!(
+            attribute(&events[0], "message.truncated"),
+            Some(&opentelemetry::Value::from(true))
+        );
+        assert_eq!(
+            attribute(&events[0], "message.original_size"),
+            Some(&opentelemetry::Value::from(
+                json!({"content": content}).to_string().len() as i64
+            ))
+        );
+        // The structure is not subject to the content limit
+        assert_eq!(
+            attribute(&events[0], "finish_reason"),
+            Some(&opentelemetry::Value::from("stop"))
+        );
+    }
+}
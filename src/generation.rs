@@ -0,0 +1,178 @@
+//! The generation span of a call, for as long as the call is in flight
+//!
+//! A call isn't over when the tracing middleware returns: a streamed response is only
+//! complete once the caller has consumed it, and a retry middleware placed *before* the
+//! tracing middleware re-sends the request through it, with the same extensions. The
+//! generation is therefore shared by the retry scope of the call (if any, see
+//! [`RetryScope`](crate::attempts::RetryScope)), every attempt and the response stream,
+//! and is only ended (together with the root span created for it) once the last of them
+//! lets go of it.
+//!
+//! Each attempt leaves its outcome (status, error and response headers) as the pending
+//! outcome of the generation, so a call that succeeds on a re-sent attempt doesn't keep
+//! the error of an earlier one. Output and usage are recorded directly, as only the final
+//! response carries them.
+
+use crate::attempts::AttemptTracker;
+use crate::config::OpenAITracingConfig;
+use crate::conventions::{ErrorRecord, ResponseRecord};
+use crate::metrics::CallMetrics;
+use opentelemetry::trace::{SpanRef, Status, TraceContextExt};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_semantic_conventions::attribute::ERROR_TYPE;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The outcome of the latest attempt, recorded on the generation once it ends
+#[derive(Default)]
+pub(crate) struct Outcome {
+    status: Status,
+    attributes: Vec<KeyValue>,
+    error_type: Option<String>,
+}
+
+impl Outcome {
+    /// Add an attribute describing the response, e.g. its status code, a header or the
+    /// `error.type`, which is also recorded on the metrics
+    pub(crate) fn set_attribute(&mut self, attribute: KeyValue) {
+        if attribute.key.as_str() == ERROR_TYPE {
+            self.error_type = Some(attribute.value.as_str().into_owned());
+        }
+        self.attributes.push(attribute);
+    }
+
+    /// Record a failure: the error status, its `error.type` and the attributes the
+    /// configured conventions describe errors with
+    pub(crate) fn set_error(&mut self, config: &OpenAITracingConfig, error: &ErrorRecord<'_>) {
+        self.set_status(Status::error(error.message.to_string()));
+        self.set_attribute(KeyValue::new(ERROR_TYPE, error.error_type.to_string()));
+        for attribute in config.error_attributes(error) {
+            self.set_attribute(attribute);
+        }
+    }
+
+    pub(crate) fn set_status(&mut self, status: Status) {
+        self.status = status;
+    }
+
+    pub(crate) fn status(&self) -> &Status {
+        &self.status
+    }
+
+    pub(crate) fn attributes(&self) -> &[KeyValue] {
+        &self.attributes
+    }
+}
+
+/// A generation in flight; cheap to clone, ended when the last clone is dropped
+#[derive(Clone)]
+pub(crate) struct Generation(Arc<GenerationInner>);
+
+struct GenerationInner {
+    /// Holds the generation span, so children (attempts, spans of inner middleware) are
+    /// parented to it
+    cx: Context,
+    root_cx: Option<Context>,
+    config: Arc<OpenAITracingConfig>,
+    operation_type: &'static str,
+    request_model: Option<String>,
+    stream_requested: bool,
+    attempts: AttemptTracker,
+    metrics: Mutex<CallMetrics>,
+    outcome: Mutex<Outcome>,
+}
+
+impl Generation {
+    /// Track the generation whose span is held by `cx`
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        cx: Context,
+        root_cx: Option<Context>,
+        attempts: AttemptTracker,
+        config: Arc<OpenAITracingConfig>,
+        operation_type: &'static str,
+        request_model: Option<String>,
+        stream_requested: bool,
+        metrics: CallMetrics,
+    ) -> Self {
+        Self(Arc::new(GenerationInner {
+            cx,
+            root_cx,
+            config,
+            operation_type,
+            request_model,
+            stream_requested,
+            attempts,
+            metrics: Mutex::new(metrics),
+            outcome: Mutex::default(),
+        }))
+    }
+
+    /// The context holding the generation span
+    pub(crate) fn context(&self) -> &Context {
+        &self.0.cx
+    }
+
+    pub(crate) fn span(&self) -> SpanRef<'_> {
+        self.0.cx.span()
+    }
+
+    pub(crate) fn config(&self) -> &Arc<OpenAITracingConfig> {
+        &self.0.config
+    }
+
+    pub(crate) fn operation_type(&self) -> &'static str {
+        self.0.operation_type
+    }
+
+    pub(crate) fn request_model(&self) -> Option<&str> {
+        self.0.request_model.as_deref()
+    }
+
+    pub(crate) fn stream_requested(&self) -> bool {
+        self.0.stream_requested
+    }
+
+    pub(crate) fn attempts(&self) -> &AttemptTracker {
+        &self.0.attempts
+    }
+
+    pub(crate) fn metrics(&self) -> MutexGuard<'_, CallMetrics> {
+        self.0.metrics.lock().unwrap()
+    }
+
+    /// Replace the outcome of the previous attempt, if any
+    pub(crate) fn set_outcome(&self, outcome: Outcome) {
+        *self.0.outcome.lock().unwrap() = outcome;
+    }
+}
+
+impl Drop for GenerationInner {
+    fn drop(&mut self) {
+        let span = self.cx.span();
+        let outcome = std::mem::take(self.outcome.get_mut().unwrap());
+        let metrics = self.metrics.get_mut().unwrap();
+        for attribute in outcome.attributes {
+            span.set_attribute(attribute);
+        }
+        span.set_status(outcome.status);
+        if let Some(error_type) = outcome.error_type {
+            metrics.set_error_type(error_type);
+        }
+
+        let attempts = self.attempts.attempts();
+        let response = ResponseRecord {
+            attempts: (attempts > 0).then_some(attempts),
+            duration: Some(metrics.start_time().elapsed()),
+            ..ResponseRecord::new(self.operation_type)
+        };
+        for attribute in self.config.response_attributes(&response) {
+            span.set_attribute(attribute);
+        }
+        metrics.finish();
+        span.end();
+
+        if let Some(root_cx) = &self.root_cx {
+            root_cx.span().end();
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Capture of response headers such as rate limits and request IDs
+//!
+//! Headers are recorded following the HTTP semantic conventions as
+//! `http.response.header.<lowercase name>` with a string array value.
+
+use http::HeaderMap;
+use opentelemetry::{KeyValue, StringValue};
+
+/// The response headers matching the allowlist.
+///
+/// Allowlist entries are case-insensitive header names; an entry ending in `*` matches
+/// every header starting with the part before it.
+pub(crate) fn captured_headers<S: AsRef<str>>(allowlist: &[S], headers: &HeaderMap) -> HeaderMap {
+    let mut captured = HeaderMap::new();
+    for (name, value) in headers {
+        // Header names are stored lowercase
+        if allowlist
+            .iter()
+            .any(|entry| matches(entry.as_ref(), name.as_str()))
+        {
+            captured.append(name, value.clone());
+        }
+    }
+    captured
+}
+
+/// Span attributes for (captured) response headers
+pub(crate) fn header_attributes(headers: &HeaderMap) -> Vec<KeyValue> {
+    let mut attributes = Vec::new();
+    for name in headers.keys() {
+        let name = name.as_str();
+        let values: Vec<StringValue> = headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(|value| StringValue::from(value.to_string()))
+            .collect();
+        if !values.is_empty() {
+            attributes.push(KeyValue::new(
+                format!("http.response.header.{}", name),
+                opentelemetry::Value::Array(values.into()),
+            ));
+        }
+    }
+    attributes
+}
+
+fn matches(entry: &str, name: &str) -> bool {
+    match entry.strip_suffix('*') {
+        Some(prefix) => name
+            .get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix)),
+        None => entry.eq_ignore_ascii_case(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist_with_prefix_entries() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining-tokens", "149990".parse().unwrap());
+        headers.insert("x-ratelimit-reset-requests", "6ms".parse().unwrap());
+        headers.insert("X-Request-ID", "req-1".parse().unwrap());
+        headers.insert("set-cookie", "secret".parse().unwrap());
+
+        let captured = captured_headers(&["x-ratelimit-*", "x-request-id"], &headers);
+        let mut attributes = header_attributes(&captured);
+        attributes.sort_by(|a, b| a.key.as_str().cmp(b.key.as_str()));
+
+        let keys: Vec<&str> = attributes.iter().map(|kv| kv.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "http.response.header.x-ratelimit-remaining-tokens",
+                "http.response.header.x-ratelimit-reset-requests",
+                "http.response.header.x-request-id",
+            ]
+        );
+        assert_eq!(
+            attributes[0].value,
+            opentelemetry::Value::Array(vec![StringValue::from("149990")].into())
+        );
+    }
+}
@@ -1,20 +1,72 @@
 //! Wrapper to make reqwest_middleware::ClientWithMiddleware work with async-openai's HttpClient trait
 
+use crate::attributes::RequestTraceAttributes;
+use crate::context::LangfuseContext;
+use crate::multipart::MultipartSummary;
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures::Stream;
+use http::Extensions;
 use reqwest::{header::HeaderMap, Method, Url};
-use reqwest_middleware::ClientWithMiddleware;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
 use std::pin::Pin;
 
 /// Wrapper struct for ClientWithMiddleware to implement HttpClient
+#[derive(Clone)]
 pub struct HttpClientWithMiddleware {
     client: ClientWithMiddleware,
+    extensions: Extensions,
 }
 
 impl HttpClientWithMiddleware {
     pub fn new(client: ClientWithMiddleware) -> Self {
-        Self { client }
+        Self {
+            client,
+            extensions: Extensions::new(),
+        }
+    }
+
+    /// Attach an extension to every request made through this client.
+    ///
+    /// The client is cheap to clone, so a copy carrying one-off extensions can be used
+    /// to build a dedicated async-openai `Client` for a specific call.
+    pub fn with_extension<T: Clone + Send + Sync + 'static>(mut self, extension: T) -> Self {
+        self.extensions.insert(extension);
+        self
+    }
+
+    /// Attach per-request trace attributes to every request made through this client
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use async_openai::{config::OpenAIConfig, Client};
+    /// use reqwest_openai_tracing::{HttpClientWithMiddleware, RequestTraceAttributes};
+    ///
+    /// # fn example(http_client: &HttpClientWithMiddleware) {
+    /// let attributes = RequestTraceAttributes::new()
+    ///     .with_name("summarize-ticket")
+    ///     .with_user_id("user-42");
+    /// let client = Client::build(
+    ///     http_client.clone().with_trace_attributes(attributes),
+    ///     OpenAIConfig::new(),
+    ///     Default::default(),
+    /// );
+    /// # }
+    /// ```
+    pub fn with_trace_attributes(self, attributes: RequestTraceAttributes) -> Self {
+        self.with_extension(attributes)
+    }
+
+    /// Read trace attributes from this context for every request made through this client
+    pub fn with_langfuse_context(self, context: LangfuseContext) -> Self {
+        self.with_extension(context)
+    }
+
+    fn request_builder(&self, method: Method, url: Url, headers: HeaderMap) -> RequestBuilder {
+        let mut request = self.client.request(method, url).headers(headers);
+        request.extensions().extend(self.extensions.clone());
+        request
     }
 }
 
@@ -28,7 +80,7 @@ impl async_openai::http_client::HttpClient for HttpClientWithMiddleware {
         headers: HeaderMap,
         body: Option<Bytes>,
     ) -> Result<async_openai::http_client::HttpResponse, async_openai::http_client::HttpError> {
-        let mut request = self.client.request(method, url).headers(headers);
+        let mut request = self.request_builder(method, url, headers);
 
         if let Some(body) = body {
             request = request.body(body);
@@ -83,20 +135,23 @@ impl async_openai::http_client::HttpClient for HttpClientWithMiddleware {
                 })?,
         );
 
-        let response = self
-            .client
-            .request(method, url)
-            .headers(headers)
-            .body(form.body)
-            .send()
-            .await
-            .map_err(|e| async_openai::http_client::HttpError {
+        // Summarize the form so the middleware can record its fields and file sizes. The
+        // form is only available encoded, so this takes one linear scan of the body for
+        // its boundaries, which spares the middleware from parsing it again
+        let mut request = self.request_builder(method, url, headers);
+        if let Some(summary) = MultipartSummary::parse(&form.body, &form.boundary) {
+            request = request.with_extension(summary);
+        }
+
+        let response = request.body(form.body).send().await.map_err(|e| {
+            async_openai::http_client::HttpError {
                 message: e.to_string(),
                 status: match &e {
                     reqwest_middleware::Error::Reqwest(re) => re.status(),
                     _ => None,
                 },
-            })?;
+            }
+        })?;
 
         let status = response.status();
         let headers = response.headers().clone();
@@ -137,7 +192,7 @@ impl async_openai::http_client::HttpClient for HttpClientWithMiddleware {
         use eventsource_stream::Eventsource;
         use futures::StreamExt;
 
-        let mut request = self.client.request(method, url).headers(headers);
+        let mut request = self.request_builder(method, url, headers);
 
         if let Some(body) = body {
             request = request.body(body);
@@ -42,20 +42,57 @@
 //! # }
 //! ```
 
+mod attempts;
 mod attributes;
+mod audio;
+mod chat;
+mod config;
 mod context;
+mod conventions;
+mod errors;
+mod events;
+mod generation;
+mod headers;
 mod http_client;
 mod langfuse;
+mod media;
+mod metrics;
 mod middleware;
+mod multipart;
+mod parameters;
+mod pricing;
+mod redaction;
+mod responses;
+mod retry;
+mod streaming;
+mod truncation;
+mod usage;
 
 // Re-export main types
-pub use attributes::{LangfuseAttributes, ObservationAttributesBuilder, TraceAttributesBuilder};
+pub use attempts::{OpenAIAttemptTracingMiddleware, OpenAIRetryScopeMiddleware};
+pub use attributes::{
+    LangfuseAttributes, ObservationAttributesBuilder, RequestTraceAttributes,
+    TraceAttributesBuilder,
+};
+pub use config::{
+    ContentCapture, OpenAITracingConfig, OpenAITracingMiddlewareBuilder, SpanNameFormatter,
+    CAPTURE_MESSAGE_CONTENT_ENV, DEFAULT_CAPTURED_HEADERS, DEFAULT_TRACER_NAME,
+};
 pub use context::{
-    add_tags, apply_context, set_session_id, set_user_id, LangfuseContext, LangfuseContextBuilder,
-    GLOBAL_CONTEXT,
+    add_tags, apply_context, set_session_id, set_user_id, with_context, LangfuseContext,
+    LangfuseContextBuilder, GLOBAL_CONTEXT,
+};
+pub use conventions::{
+    AttributeConvention, ErrorRecord, InterTokenLatency, LangfuseConvention, OpenAIConvention,
+    OpenInferenceConvention, OpenLlmetryConvention, OtelGenAiConvention, RequestRecord,
+    ResponseRecord, TraceRecord,
 };
 pub use http_client::HttpClientWithMiddleware;
+pub use media::{DirectoryMediaSink, MediaReference, MediaSink};
 pub use middleware::OpenAITracingMiddleware;
+pub use pricing::{ModelPrice, PricingTable};
+pub use redaction::{ContentKind, JsonPathRedactor, Redactor, RegexRedactor, SuppressContent};
+pub use retry::OpenAIRetryMiddleware;
 
 // Re-export context module for convenient access
 pub mod langfuse_context {
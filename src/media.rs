@@ -0,0 +1,296 @@
+//! Inline media in recorded content
+//!
+//! Vision and audio requests embed their media in the JSON body: `image_url` parts with
+//! `data:image/png;base64,...` URLs, `input_audio` parts with base64 `data`, and responses
+//! carry generated audio and images the same way. Recorded as is, a single image makes a
+//! span attribute several megabytes. Inline media is therefore replaced by a placeholder
+//! recording its content type, size and SHA-256 hash, so traces stay small but still show
+//! which media was sent:
+//!
+//! ```json
+//! {"type": "image_url", "image_url": {"url": {"content_type": "image/png", "size_bytes": 48213, "sha256": "9f86d0…"}}}
+//! ```
+//!
+//! The media itself can be kept by a [`MediaSink`], configured with
+//! [`with_media_sink`](crate::OpenAITracingMiddlewareBuilder::with_media_sink); the
+//! reference it returns is recorded in the placeholder as `uri`.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// Fields holding base64 media without a data URL, with the content type of their media
+/// when it isn't given by a sibling `format` field
+const BASE64_FIELDS: &[(&str, &str, &str)] = &[
+    // {"input_audio": {"data": "...", "format": "wav"}} and {"audio": {"data": "..."}}
+    ("input_audio", "data", "audio"),
+    ("audio", "data", "audio"),
+];
+
+/// Image generation responses: {"data": [{"b64_json": "..."}]}
+const B64_JSON_FIELD: &str = "b64_json";
+
+/// Media found inline in recorded content
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MediaReference {
+    /// MIME type of the media, e.g. `image/png`
+    pub content_type: String,
+    /// Size in bytes of the decoded media
+    pub size: usize,
+    /// Hex-encoded SHA-256 hash of the decoded media
+    pub sha256: String,
+}
+
+impl MediaReference {
+    fn new(content_type: &str, data: &[u8]) -> Self {
+        let sha256 = Sha256::digest(data)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Self {
+            content_type: content_type.to_string(),
+            size: data.len(),
+            sha256,
+        }
+    }
+
+    /// File extension for the content type, e.g. `png` for `image/png`
+    pub fn extension(&self) -> &str {
+        match self
+            .content_type
+            .split_once('/')
+            .map(|(_, subtype)| subtype)
+        {
+            Some("jpeg") => "jpg",
+            Some("mpeg") => "mp3",
+            Some("x-wav") => "wav",
+            Some(subtype) if !subtype.is_empty() && subtype.chars().all(char::is_alphanumeric) => {
+                subtype
+            }
+            _ => "bin",
+        }
+    }
+}
+
+/// Keeps the media replaced in recorded content, e.g. on disk or in a media store
+///
+/// `store` is called while the request or response is recorded, so slow stores should hand
+/// the media off (e.g. to a background task) rather than upload it inline.
+pub trait MediaSink: Send + Sync {
+    /// Store the media, returning a reference to record in its placeholder, if any
+    fn store(&self, media: &MediaReference, data: &[u8]) -> Option<String>;
+}
+
+impl<F> MediaSink for F
+where
+    F: Fn(&MediaReference, &[u8]) -> Option<String> + Send + Sync,
+{
+    fn store(&self, media: &MediaReference, data: &[u8]) -> Option<String> {
+        self(media, data)
+    }
+}
+
+/// Writes media to a directory, named by its hash (e.g. `9f86d0….png`), and records the
+/// path of the file
+///
+/// Media sent repeatedly is written once. Failures to write are ignored: the placeholder
+/// is then recorded without a path.
+#[derive(Clone, Debug)]
+pub struct DirectoryMediaSink {
+    directory: PathBuf,
+}
+
+impl DirectoryMediaSink {
+    /// Store media in `directory`, which is created when needed
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl MediaSink for DirectoryMediaSink {
+    fn store(&self, media: &MediaReference, data: &[u8]) -> Option<String> {
+        let path = self
+            .directory
+            .join(format!("{}.{}", media.sha256, media.extension()));
+        if !path.exists() {
+            std::fs::create_dir_all(&self.directory).ok()?;
+            std::fs::write(&path, data).ok()?;
+        }
+        Some(path.to_string_lossy().into_owned())
+    }
+}
+
+/// Replace the inline media in `content` with placeholders, storing it in `sink` if given
+pub(crate) fn replace_inline_media(content: Value, sink: Option<&dyn MediaSink>) -> Value {
+    match content {
+        Value::String(s) => match parse_data_url(&s) {
+            Some((content_type, data)) => placeholder(&content_type, &data, sink),
+            None => Value::String(s),
+        },
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| replace_inline_media(item, sink))
+                .collect(),
+        ),
+        Value::Object(mut fields) => {
+            for (parent, field, media_type) in BASE64_FIELDS {
+                let Some(Value::Object(media)) = fields.get_mut(*parent) else {
+                    continue;
+                };
+                let content_type = match media.get("format").and_then(Value::as_str) {
+                    Some(format) => format!("{}/{}", media_type, format),
+                    None => format!("{}/unknown", media_type),
+                };
+                replace_base64_field(media, field, &content_type, sink);
+            }
+            replace_base64_field(&mut fields, B64_JSON_FIELD, "image/png", sink);
+
+            Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(key, value)| (key, replace_inline_media(value, sink)))
+                    .collect(),
+            )
+        }
+        value => value,
+    }
+}
+
+/// Replace the base64 string in `fields[field]`, if it decodes, with its placeholder
+fn replace_base64_field(
+    fields: &mut serde_json::Map<String, Value>,
+    field: &str,
+    content_type: &str,
+    sink: Option<&dyn MediaSink>,
+) {
+    let Some(Value::String(encoded)) = fields.get(field) else {
+        return;
+    };
+    if let Ok(data) = STANDARD.decode(encoded) {
+        fields.insert(field.to_string(), placeholder(content_type, &data, sink));
+    }
+}
+
+/// The content type and decoded data of a base64 `data:` URL
+fn parse_data_url(url: &str) -> Option<(String, Vec<u8>)> {
+    let (header, encoded) = url.strip_prefix("data:")?.split_once(',')?;
+    let content_type = header.strip_suffix(";base64")?;
+    // Parameters such as `;charset=utf-8` precede the encoding
+    let content_type = content_type.split(';').next().unwrap_or_default();
+    let content_type = if content_type.is_empty() {
+        "application/octet-stream"
+    } else {
+        content_type
+    };
+    let data = STANDARD.decode(encoded).ok()?;
+    Some((content_type.to_string(), data))
+}
+
+fn placeholder(content_type: &str, data: &[u8], sink: Option<&dyn MediaSink>) -> Value {
+    let media = MediaReference::new(content_type, data);
+    let uri = sink.and_then(|sink| sink.store(&media, data));
+    let mut placeholder = json!({
+        "content_type": media.content_type,
+        "size_bytes": media.size,
+        "sha256": media.sha256,
+    });
+    if let Some(uri) = uri {
+        placeholder["uri"] = Value::String(uri);
+    }
+    placeholder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // SHA-256 of "hello"
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_data_urls_and_audio_are_replaced() {
+        let hello = STANDARD.encode("hello");
+        let content = json!({"messages": [{"role": "user", "content": [
+            {"type": "text", "text": "What is this?"},
+            {"type": "image_url", "image_url": {"url": format!("data:image/png;base64,{}", hello), "detail": "low"}},
+            {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}},
+            {"type": "input_audio", "input_audio": {"data": hello, "format": "wav"}}
+        ]}]});
+
+        let replaced = replace_inline_media(content, None);
+        let parts = &replaced["messages"][0]["content"];
+        assert_eq!(parts[0]["text"], "What is this?");
+        assert_eq!(
+            parts[1]["image_url"],
+            json!({
+                "url": {"content_type": "image/png", "size_bytes": 5, "sha256": HELLO_SHA256},
+                "detail": "low"
+            })
+        );
+        assert_eq!(parts[2]["image_url"]["url"], "https://example.com/cat.png");
+        assert_eq!(
+            parts[3]["input_audio"],
+            json!({
+                "data": {"content_type": "audio/wav", "size_bytes": 5, "sha256": HELLO_SHA256},
+                "format": "wav"
+            })
+        );
+    }
+
+    #[test]
+    fn test_generated_media_is_replaced() {
+        let hello = STANDARD.encode("hello");
+        let images = replace_inline_media(json!({"data": [{"b64_json": hello}]}), None);
+        assert_eq!(images["data"][0]["b64_json"]["content_type"], "image/png");
+
+        let message = replace_inline_media(
+            json!({"role": "assistant", "audio": {"id": "audio_1", "data": hello, "transcript": "Hi"}}),
+            None,
+        );
+        assert_eq!(message["audio"]["data"]["content_type"], "audio/unknown");
+        assert_eq!(message["audio"]["transcript"], "Hi");
+    }
+
+    #[test]
+    fn test_invalid_base64_is_kept() {
+        let content = json!({"url": "data:image/png;base64,not base64!", "audio": {"data": "%%"}});
+        assert_eq!(replace_inline_media(content.clone(), None), content);
+    }
+
+    #[test]
+    fn test_sink_receives_media() {
+        let stored = Mutex::new(Vec::new());
+        let sink = |media: &MediaReference, data: &[u8]| {
+            stored.lock().unwrap().push((media.clone(), data.to_vec()));
+            Some(format!("media://{}", media.sha256))
+        };
+        let content =
+            json!({"url": format!("data:image/jpeg;base64,{}", STANDARD.encode("hello"))});
+
+        let replaced = replace_inline_media(content, Some(&sink));
+        assert_eq!(replaced["url"]["uri"], format!("media://{}", HELLO_SHA256));
+        let stored = stored.into_inner().unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].0.extension(), "jpg");
+        assert_eq!(stored[0].1, b"hello");
+    }
+
+    #[test]
+    fn test_directory_sink_writes_file_once() {
+        let directory = std::env::temp_dir().join(format!("openai-media-{}", std::process::id()));
+        let sink = DirectoryMediaSink::new(&directory);
+        let media = MediaReference::new("image/png", b"hello");
+
+        let path = sink.store(&media, b"hello").unwrap();
+        assert!(path.ends_with(&format!("{}.png", HELLO_SHA256)));
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello");
+        assert_eq!(sink.store(&media, b"hello"), Some(path));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+//! GenAI client metrics
+//!
+//! Alongside spans the middleware records the client metrics of the GenAI semantic
+//! conventions, so latency and token consumption can be monitored and alerted on without
+//! querying traces:
+//!
+//! - `gen_ai.client.token.usage`: input and output tokens per call, by `gen_ai.token.type`
+//! - `gen_ai.client.operation.duration`: duration of calls in seconds, with `error.type`
+//!   for failed calls
+//! - `gen_ai.client.time_to_first_token`: time in seconds until the first output of a
+//!   streamed call arrived
+//!
+//! Measurements carry the operation, provider, request and response model and server
+//! address of the call.
+
+use crate::usage::Usage;
+use opentelemetry::metrics::{Histogram, Meter};
+use opentelemetry::KeyValue;
+use opentelemetry_semantic_conventions::attribute::{
+    ERROR_TYPE, GEN_AI_OPERATION_NAME, GEN_AI_REQUEST_MODEL, GEN_AI_RESPONSE_MODEL, GEN_AI_SYSTEM,
+    GEN_AI_TOKEN_TYPE, SERVER_ADDRESS, SERVER_PORT,
+};
+use opentelemetry_semantic_conventions::metric::{
+    GEN_AI_CLIENT_OPERATION_DURATION, GEN_AI_CLIENT_TOKEN_USAGE,
+};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Time until the first output of a streamed call, the client-side counterpart of
+/// `gen_ai.server.time_to_first_token`
+pub(crate) const GEN_AI_CLIENT_TIME_TO_FIRST_TOKEN: &str = "gen_ai.client.time_to_first_token";
+
+/// Bucket boundaries advised by the semantic conventions
+const TOKEN_USAGE_BUCKETS: &[f64] = &[
+    1.0, 4.0, 16.0, 64.0, 256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0,
+    16777216.0, 67108864.0,
+];
+const DURATION_BUCKETS: &[f64] = &[
+    0.01, 0.02, 0.04, 0.08, 0.16, 0.32, 0.64, 1.28, 2.56, 5.12, 10.24, 20.48, 40.96, 81.92,
+];
+const TIME_TO_FIRST_TOKEN_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.02, 0.04, 0.06, 0.08, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0,
+];
+
+/// The instruments metrics are recorded with
+pub(crate) struct GenAiMetrics {
+    token_usage: Histogram<u64>,
+    operation_duration: Histogram<f64>,
+    time_to_first_token: Histogram<f64>,
+}
+
+impl GenAiMetrics {
+    pub(crate) fn new(meter: &Meter) -> Self {
+        Self {
+            token_usage: meter
+                .u64_histogram(GEN_AI_CLIENT_TOKEN_USAGE)
+                .with_description("Number of input and output tokens used")
+                .with_unit("{token}")
+                .with_boundaries(TOKEN_USAGE_BUCKETS.to_vec())
+                .build(),
+            operation_duration: meter
+                .f64_histogram(GEN_AI_CLIENT_OPERATION_DURATION)
+                .with_description("GenAI operation duration")
+                .with_unit("s")
+                .with_boundaries(DURATION_BUCKETS.to_vec())
+                .build(),
+            time_to_first_token: meter
+                .f64_histogram(GEN_AI_CLIENT_TIME_TO_FIRST_TOKEN)
+                .with_description("Time to receive the first output of a streamed call")
+                .with_unit("s")
+                .with_boundaries(TIME_TO_FIRST_TOKEN_BUCKETS.to_vec())
+                .build(),
+        }
+    }
+}
+
+/// Measurements of one call, recorded as its outcome becomes known
+pub(crate) struct CallMetrics {
+    metrics: Arc<GenAiMetrics>,
+    attributes: Vec<KeyValue>,
+    start_time: Instant,
+    error_type: Option<String>,
+    first_token_recorded: bool,
+}
+
+impl CallMetrics {
+    pub(crate) fn new(
+        metrics: Arc<GenAiMetrics>,
+        operation_type: &str,
+        request_model: Option<&str>,
+        url: &reqwest::Url,
+        start_time: Instant,
+    ) -> Self {
+        let mut attributes = vec![
+            KeyValue::new(GEN_AI_OPERATION_NAME, operation_type.to_string()),
+            KeyValue::new(GEN_AI_SYSTEM, "openai"),
+        ];
+        if let Some(model) = request_model {
+            attributes.push(KeyValue::new(GEN_AI_REQUEST_MODEL, model.to_string()));
+        }
+        if let Some(host) = url.host_str() {
+            attributes.push(KeyValue::new(SERVER_ADDRESS, host.to_string()));
+        }
+        if let Some(port) = url.port_or_known_default() {
+            attributes.push(KeyValue::new(SERVER_PORT, port as i64));
+        }
+        Self {
+            metrics,
+            attributes,
+            start_time,
+            error_type: None,
+            first_token_recorded: false,
+        }
+    }
+
+    /// When the call started
+    pub(crate) fn start_time(&self) -> Instant {
+        self.start_time
+    }
+
+    /// The model that served the call
+    pub(crate) fn set_response_model(&mut self, model: &str) {
+        self.attributes
+            .retain(|kv| kv.key.as_str() != GEN_AI_RESPONSE_MODEL);
+        self.attributes
+            .push(KeyValue::new(GEN_AI_RESPONSE_MODEL, model.to_string()));
+    }
+
+    /// Mark the call as failed; should be a low-cardinality value such as an error code
+    pub(crate) fn set_error_type(&mut self, error_type: impl Into<String>) {
+        self.error_type = Some(error_type.into());
+    }
+
+    /// Record the input and output tokens of the call
+    pub(crate) fn record_usage(&self, usage: &Usage) {
+        for (token_type, count) in [("input", usage.input), ("output", usage.output)] {
+            let Some(count) = count.filter(|count| *count >= 0) else {
+                continue;
+            };
+            let mut attributes = self.attributes.clone();
+            attributes.push(KeyValue::new(GEN_AI_TOKEN_TYPE, token_type));
+            self.metrics.token_usage.record(count as u64, &attributes);
+        }
+    }
+
+    /// Record the time to the first output, once per call
+    pub(crate) fn record_first_token(&mut self) {
+        if self.first_token_recorded {
+            return;
+        }
+        self.first_token_recorded = true;
+        self.metrics
+            .time_to_first_token
+            .record(self.start_time.elapsed().as_secs_f64(), &self.attributes);
+    }
+
+    /// Record the duration of the completed call
+    pub(crate) fn finish(&mut self) {
+        if let Some(error_type) = self.error_type.take() {
+            self.attributes.push(KeyValue::new(ERROR_TYPE, error_type));
+        }
+        self.metrics
+            .operation_duration
+            .record(self.start_time.elapsed().as_secs_f64(), &self.attributes);
+    }
+}
+
+/// Low-cardinality `error.type` of a failed request
+pub(crate) fn request_error_type(error: &reqwest_middleware::Error) -> &'static str {
+    match error {
+        reqwest_middleware::Error::Reqwest(e) => reqwest_error_type(e),
+        reqwest_middleware::Error::Middleware(_) => "_OTHER",
+    }
+}
+
+/// Low-cardinality `error.type` of a failed request or response body
+pub(crate) fn reqwest_error_type(error: &reqwest::Error) -> &'static str {
+    if error.is_timeout() {
+        "timeout"
+    } else if error.is_connect() {
+        "connect"
+    } else {
+        "_OTHER"
+    }
+}
@@ -1,22 +1,33 @@
-use crate::attributes::TraceAttributesBuilder;
+use crate::attempts::{AttemptSpan, AttemptTracker, RetryScope};
+use crate::attributes::{merge_attributes, RequestTraceAttributes, TraceAttributesBuilder};
+use crate::audio;
+use crate::config::{OpenAITracingConfig, OpenAITracingMiddlewareBuilder};
+use crate::context::LangfuseContext;
+use crate::conventions::{ErrorRecord, RequestRecord, ResponseRecord, TraceRecord};
+use crate::errors::ErrorResponse;
+use crate::events;
+use crate::generation::{Generation, Outcome};
+use crate::headers::captured_headers;
+use crate::metrics::{request_error_type, reqwest_error_type, CallMetrics};
+use crate::multipart::MultipartSummary;
+use crate::redaction::ContentKind;
+use crate::streaming::TracedStream;
+use crate::usage::Usage;
 use http::Extensions;
-use opentelemetry::trace::{FutureExt, Span, SpanKind, Status, TraceContextExt, Tracer};
-use opentelemetry::{global, Context, KeyValue};
-use opentelemetry_semantic_conventions::attribute::{
-    ERROR_TYPE, GEN_AI_OPERATION_NAME, GEN_AI_REQUEST_MODEL, GEN_AI_SYSTEM,
-    HTTP_RESPONSE_STATUS_CODE,
-};
-use opentelemetry_semantic_conventions::attribute::{
-    GEN_AI_USAGE_INPUT_TOKENS, GEN_AI_USAGE_OUTPUT_TOKENS,
-};
-use reqwest::{Request, Response};
+use opentelemetry::trace::{FutureExt, Span, SpanKind, SpanRef, Status, TraceContextExt, Tracer};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_semantic_conventions::attribute::HTTP_RESPONSE_STATUS_CODE;
+use reqwest::{Request, Response, ResponseBuilderExt};
 use reqwest_middleware::{Middleware, Next, Result};
 use serde_json::{json, Value};
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 
 /// Middleware that automatically creates OpenTelemetry spans for OpenAI API calls
-#[allow(dead_code)]
-pub struct OpenAITracingMiddleware;
+#[derive(Clone)]
+pub struct OpenAITracingMiddleware {
+    config: Arc<OpenAITracingConfig>,
+}
 
 impl Default for OpenAITracingMiddleware {
     fn default() -> Self {
@@ -25,13 +36,35 @@ impl Default for OpenAITracingMiddleware {
 }
 
 impl OpenAITracingMiddleware {
-    #[allow(dead_code)]
+    /// Create a middleware with the default configuration, capturing content as set
+    /// with [`CAPTURE_MESSAGE_CONTENT_ENV`](crate::CAPTURE_MESSAGE_CONTENT_ENV)
     pub fn new() -> Self {
-        Self
+        Self::builder().build()
+    }
+
+    /// Create a middleware with the given configuration
+    pub fn with_config(mut config: OpenAITracingConfig) -> Self {
+        config.resolve_tracer();
+        config.resolve_metrics();
+        Self {
+            config: Arc::new(config),
+        }
+    }
+
+    /// Start building a configured middleware
+    pub fn builder() -> OpenAITracingMiddlewareBuilder {
+        OpenAITracingMiddlewareBuilder::new()
     }
 
-    fn extract_operation_from_path(path: &str) -> (&str, &str) {
-        if path.contains("/chat/completions") {
+    /// The configuration of this middleware
+    pub fn config(&self) -> &OpenAITracingConfig {
+        &self.config
+    }
+
+    fn extract_operation_from_path(path: &str) -> (&'static str, &'static str) {
+        if path.contains("/responses") {
+            ("response", "responses")
+        } else if path.contains("/chat/completions") {
             ("chat", "chat.completions")
         } else if path.contains("/completions") {
             ("completion", "completions")
@@ -39,10 +72,48 @@ impl OpenAITracingMiddleware {
             ("embedding", "embeddings")
         } else if path.contains("/images/generations") {
             ("image", "images.generations")
+        } else if path.contains("/audio/transcriptions") {
+            ("transcription", "audio.transcriptions")
+        } else if path.contains("/audio/translations") {
+            ("translation", "audio.translations")
+        } else if path.contains("/audio/speech") {
+            ("speech", "audio.speech")
         } else {
             ("unknown", "unknown")
         }
     }
+
+    /// The context to read trace attributes from: one attached to the request's extensions,
+    /// otherwise the active (task-local or configured) context
+    fn request_context(&self, extensions: &Extensions) -> LangfuseContext {
+        extensions
+            .get::<LangfuseContext>()
+            .cloned()
+            .unwrap_or_else(|| self.config.active_context())
+    }
+
+    /// The configuration for a request: that of the middleware, with the redactors of the
+    /// request's context added
+    fn request_config(&self, extensions: &Extensions) -> Arc<OpenAITracingConfig> {
+        let redactors = self.request_context(extensions).redactors();
+        if redactors.is_empty() {
+            return self.config.clone();
+        }
+        let mut config = (*self.config).clone();
+        config.redactors.extend(redactors);
+        Arc::new(config)
+    }
+
+    /// Trace attributes of a request: those of its context, overridden by any
+    /// [`RequestTraceAttributes`] attached to the request
+    fn trace_attributes(&self, extensions: &Extensions) -> Vec<KeyValue> {
+        let context_attrs = self.request_context(extensions).get_attributes();
+        let request_attrs = extensions
+            .get::<RequestTraceAttributes>()
+            .map(|attrs| attrs.trace_attributes())
+            .unwrap_or_default();
+        merge_attributes([context_attrs, request_attrs])
+    }
 }
 
 #[async_trait::async_trait]
@@ -53,9 +124,23 @@ impl Middleware for OpenAITracingMiddleware {
         extensions: &mut Extensions,
         next: Next<'_>,
     ) -> Result<Response> {
-        let tracer = global::tracer("openai-middleware");
+        let tracer = self.config.tracer();
         let start_time = Instant::now();
 
+        // Behind a retry middleware every attempt of a call passes through here within the
+        // same retry scope: record later attempts as children of the generation of the first
+        if let Some(generation) = extensions
+            .get::<RetryScope>()
+            .and_then(RetryScope::generation)
+        {
+            let attempt = generation.attempts().start_attempt();
+            let cx = generation.context().clone();
+            return self
+                .send_attempt(req, extensions, next, &generation, Some(attempt))
+                .with_context(cx)
+                .await;
+        }
+
         // Extract request information
         let path = req.url().path().to_string();
         let (operation_type, operation_name) = Self::extract_operation_from_path(&path);
@@ -68,50 +153,57 @@ impl Middleware for OpenAITracingMiddleware {
         let parent_span = current_context.span();
 
         // Check if we need to create a root trace and handle it
-        if !parent_span.span_context().is_valid() {
+        if self.config.create_root_span && !parent_span.span_context().is_valid() {
             // No active span - create a root trace for Langfuse
-            // Check if trace name is set in context, otherwise use Python SDK default
-            let trace_name = crate::context::GLOBAL_CONTEXT
-                .get_attribute(crate::attributes::LangfuseAttributes::TRACE_NAME)
+            // Check if trace name is set on the request or in context, otherwise use Python SDK default
+            let trace_name = extensions
+                .get::<RequestTraceAttributes>()
+                .and_then(|attrs| attrs.name().map(str::to_string))
+                .or_else(|| {
+                    self.request_context(extensions)
+                        .get_attribute(crate::attributes::LangfuseAttributes::TRACE_NAME)
+                })
                 .unwrap_or_else(|| "OpenAI-generation".to_string());
 
             // Build attributes using the builder pattern
             let builder = TraceAttributesBuilder::new().with_name(trace_name.clone());
-            let mut root_attributes = builder.build();
 
-            // Apply any programmatically-set context attributes to the root span
-            let context_attrs = crate::context::GLOBAL_CONTEXT.get_attributes();
-            root_attributes.extend(context_attrs);
+            // Apply any programmatically-set context and request attributes to the root
+            // span, named by the configured attribute conventions
+            let trace_attributes =
+                merge_attributes([builder.build(), self.trace_attributes(extensions)]);
+            let root_attributes = self
+                .request_config(extensions)
+                .trace_attributes(&TraceRecord {
+                    attributes: &trace_attributes,
+                });
 
             let root_span = tracer
                 .span_builder(trace_name)
                 .with_kind(SpanKind::Internal)
                 .with_attributes(root_attributes)
-                .start(&tracer);
+                .start(tracer.as_ref());
 
             // Make it the current context
             let cx = Context::current_with_span(root_span);
 
-            // Process the request in the new span context using with_context
-            let result = self
-                .process_request_with_attributes(
-                    req,
-                    extensions,
-                    next,
-                    operation_type,
-                    operation_name,
-                    &path,
-                    start_time,
-                )
-                .with_context(cx.clone())
-                .await;
-
-            // End the root span
-            cx.span().end();
-
-            result
+            // Process the request in the new span context using with_context.
+            // The root span is handed over so it can be ended once the response
+            // (possibly a stream) has been fully consumed.
+            self.process_request_with_attributes(
+                req,
+                extensions,
+                next,
+                operation_type,
+                operation_name,
+                &path,
+                start_time,
+                Some(cx.clone()),
+            )
+            .with_context(cx)
+            .await
         } else {
-            // We have a parent span, use it
+            // We have a parent span (or root spans are disabled), use the current context
             self.process_request_with_attributes(
                 req,
                 extensions,
@@ -120,6 +212,7 @@ impl Middleware for OpenAITracingMiddleware {
                 operation_name,
                 &path,
                 start_time,
+                None,
             )
             .await
         }
@@ -133,16 +226,23 @@ impl OpenAITracingMiddleware {
         req: Request,
         extensions: &mut Extensions,
         next: Next<'_>,
-        operation_type: &str,
+        operation_type: &'static str,
         operation_name: &str,
         path: &str,
         start_time: Instant,
+        root_cx: Option<Context>,
     ) -> Result<Response> {
-        let tracer = global::tracer("openai-middleware");
+        let tracer = self.config.tracer();
+        let config = self.request_config(extensions);
 
         // Try to extract and parse the request body to get the actual input
         let mut model: Option<String> = None;
         let mut observation_input: Option<Value> = None;
+        let mut parameters = serde_json::Map::new();
+        let mut message_events = Vec::new();
+        let mut voice: Option<String> = None;
+        let mut form: Option<MultipartSummary> = None;
+        let mut stream_requested = false;
 
         // Try to extract deployment/model from URL for Azure
         // Azure URL format: .../openai/deployments/{deployment-id}/chat/completions
@@ -169,9 +269,20 @@ impl OpenAITracingMiddleware {
                         }
                     }
 
+                    stream_requested = json
+                        .get("stream")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false);
+
+                    // Sampling and output parameters (temperature, max tokens, ...)
+                    parameters = crate::parameters::extract_model_parameters(&json);
+
                     // Store the input for the observation based on operation type
                     observation_input = match operation_type {
                         "chat" => {
+                            if config.message_events() {
+                                message_events = events::message_events(&config, &json);
+                            }
                             // Chat completions: extract messages
                             json.get("messages").map(|messages| {
                                 json!({
@@ -195,6 +306,18 @@ impl OpenAITracingMiddleware {
                                 })
                             })
                         }
+                        "response" => {
+                            // Responses API: extract instructions and input items
+                            crate::responses::extract_input(&json)
+                        }
+                        "speech" => {
+                            // Text to speech: extract input text and voice
+                            voice = json
+                                .get("voice")
+                                .and_then(|v| v.as_str())
+                                .map(str::to_string);
+                            audio::speech_input(&json)
+                        }
                         "image" => {
                             // Image generation: extract prompt and parameters
                             let mut image_input = serde_json::Map::new();
@@ -219,209 +342,2235 @@ impl OpenAITracingMiddleware {
             }
         }
 
-        // Create span with OpenAI-specific attributes following Langfuse Python SDK patterns
-        let mut attributes = vec![
-            // OpenAI/LLM specific attributes (using semantic conventions)
-            KeyValue::new(GEN_AI_SYSTEM, "openai"),
-            KeyValue::new(GEN_AI_OPERATION_NAME, operation_type.to_string()),
-            // Langfuse observation attributes (matching Python SDK)
-            KeyValue::new("langfuse.observation.type", "generation"),
-        ];
-
-        // Only add model attributes if we could determine the model
-        if let Some(ref model_name) = model {
-            // Use semantic convention for model
-            attributes.push(KeyValue::new(GEN_AI_REQUEST_MODEL, model_name.clone()));
-            // Also add Langfuse-specific model attribute (matching Python SDK)
-            attributes.push(KeyValue::new(
-                "langfuse.observation.model.name",
-                model_name.clone(),
-            ));
-        }
-
-        // Add observation input if available
-        if let Some(ref input) = observation_input {
-            attributes.push(KeyValue::new(
-                "langfuse.observation.input",
-                input.to_string(),
-            ));
-        }
-
-        // Apply any attributes from the global LangfuseContext (matching Python SDK behavior)
+        // Transcriptions and translations upload a multipart form: use the summary attached
+        // by HttpClientWithMiddleware, or parse the buffered form body
+        if matches!(operation_type, "transcription" | "translation") {
+            form = extensions.get::<MultipartSummary>().cloned().or_else(|| {
+                let content_type = req
+                    .headers()
+                    .get(http::header::CONTENT_TYPE)?
+                    .to_str()
+                    .ok()?;
+                let body = req.body()?.as_bytes()?;
+                MultipartSummary::parse_with_content_type(body, content_type)
+            });
+            if let Some(form) = &form {
+                if let Some(model_name) = form.field("model").filter(|m| !m.is_empty()) {
+                    model = Some(model_name.to_string());
+                }
+                observation_input = Some(audio::transcription_input(form));
+                parameters = audio::transcription_parameters(form);
+            }
+        }
+
+        // Create span with the operation, model, parameters and input named by the
+        // configured attribute conventions
+        let input =
+            observation_input.and_then(|input| config.record_content(ContentKind::Input, input));
+        let prompt = extensions
+            .get::<RequestTraceAttributes>()
+            .and_then(RequestTraceAttributes::prompt);
+        let attributes = config.request_attributes(&RequestRecord {
+            model: model.as_deref(),
+            input: input.as_ref(),
+            prompt_name: prompt.map(|(name, _)| name),
+            prompt_version: prompt.and_then(|(_, version)| version),
+            audio_language: form.as_ref().and_then(|form| form.field("language")),
+            audio_file_size: form.as_ref().and_then(audio::audio_file).map(|f| f.size),
+            voice: voice.as_deref(),
+            ..RequestRecord::new(operation_type, &parameters)
+        });
+
+        // Apply any attributes from the active LangfuseContext (matching Python SDK behavior)
         // Note: These must be set programmatically via langfuse_context functions
         // This matches the Python SDK which requires calling langfuse_context.update_current_trace()
-        let context_attrs = crate::context::GLOBAL_CONTEXT.get_attributes();
-        attributes.extend(context_attrs);
+        // Per-request attributes attached through extensions take precedence
+        let trace_attributes = self.trace_attributes(extensions);
+        let attributes = merge_attributes([
+            attributes,
+            config.trace_attributes(&TraceRecord {
+                attributes: &trace_attributes,
+            }),
+        ]);
+
+        let metrics = CallMetrics::new(
+            self.config.metrics(),
+            operation_type,
+            model.as_deref(),
+            req.url(),
+            start_time,
+        );
 
         let mut span = tracer
-            .span_builder(format!("OpenAI {}", operation_name))
+            .span_builder(self.config.span_name(operation_name, model.as_deref()))
             .with_kind(SpanKind::Client)
             .with_attributes(attributes)
-            .start(&tracer);
+            .start(tracer.as_ref());
+        for (name, attributes) in message_events {
+            span.add_event(name, attributes);
+        }
+        let cx = Context::current_with_span(span);
+
+        // The generation (and root span) end once the last attempt and the response stream
+        // are done with it, see `Generation`
+        let attempts = AttemptTracker::new(tracer.clone(), cx.clone());
+        let generation = Generation::new(
+            cx.clone(),
+            root_cx,
+            attempts.clone(),
+            config,
+            operation_type,
+            model,
+            stream_requested,
+            metrics,
+        );
+        if let Some(scope) = extensions.get::<RetryScope>() {
+            scope.set_generation(generation.clone());
+        }
+
+        // Execute the request. Attempt tracing middleware placed after a retry middleware
+        // records each attempt as a child of this span.
+        extensions.insert(attempts);
+        let response = self
+            .send_attempt(req, extensions, next, &generation, None)
+            .with_context(cx)
+            .await;
+        extensions.remove::<AttemptTracker>();
+        response
+    }
 
-        // Execute the request
+    /// Send an attempt of the request of `generation` and record its response; `attempt`
+    /// is the span of an attempt re-sent by a retry middleware placed before this one
+    async fn send_attempt(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+        generation: &Generation,
+        attempt: Option<AttemptSpan>,
+    ) -> Result<Response> {
+        let start = SystemTime::now();
+        let config = generation.config();
+        let operation_type = generation.operation_type();
+        let mut outcome = Outcome::default();
         let response = next.run(req, extensions).await;
 
         // Record response information
         let response = match response {
-            Ok(res) => {
+            Ok(mut res) => {
                 let status = res.status();
-                span.set_attribute(KeyValue::new(
+                outcome.set_attribute(KeyValue::new(
                     HTTP_RESPONSE_STATUS_CODE,
                     status.as_u16() as i64,
                 ));
+                // Rate limits and request IDs, also (especially) for error responses
+                let headers = captured_headers(&self.config.captured_headers, res.headers());
+                let response = ResponseRecord {
+                    headers: Some(&headers),
+                    ..ResponseRecord::new(operation_type)
+                };
+                for attribute in config.response_attributes(&response) {
+                    outcome.set_attribute(attribute);
+                }
 
                 if status.is_success() {
-                    span.set_status(Status::Ok);
+                    let is_event_stream = res
+                        .headers()
+                        .get(http::header::CONTENT_TYPE)
+                        .and_then(|v| v.to_str().ok())
+                        .is_some_and(|v| v.starts_with("text/event-stream"));
+
+                    if generation.stream_requested() || is_event_stream {
+                        // Pass the body through as a stream, which records the output and
+                        // holds on to the generation until it finishes or is dropped
+                        let head = ResponseHead::take(&mut res);
+                        let stream =
+                            TracedStream::new(Box::pin(res.bytes_stream()), generation.clone());
+                        Ok(head.into_response(reqwest::Body::wrap_stream(stream)))
+                    } else {
+                        // Try to parse response body to set output and token usage
+                        // Buffer the response body to parse it, keeping everything else
+                        // (headers, version, url, extensions) for the rebuilt response
+                        let head = ResponseHead::take(&mut res);
+                        match res.bytes().await {
+                            Ok(bytes) => {
+                                outcome.set_status(Status::Ok);
+
+                                // Parse the response
+                                record_response_body(
+                                    &generation.span(),
+                                    &mut generation.metrics(),
+                                    config,
+                                    operation_type,
+                                    generation.request_model(),
+                                    &head.headers,
+                                    &bytes,
+                                );
 
-                    // Try to parse response body to set output and token usage
-                    // Buffer the response body to parse it
+                                // Reconstruct the response with the buffered body
+                                Ok(head.into_response(bytes.into()))
+                            }
+                            Err(e) => {
+                                outcome.set_error(
+                                    config,
+                                    &ErrorRecord::new(
+                                        operation_type,
+                                        &format!("Failed to read response body: {}", e),
+                                        reqwest_error_type(&e),
+                                    ),
+                                );
+                                Err(reqwest_middleware::Error::Reqwest(e))
+                            }
+                        }
+                    }
+                } else {
+                    // Buffer the error body to record what went wrong; the caller still
+                    // receives it unchanged, or the error response without a body if it
+                    // can't be read
+                    let head = ResponseHead::take(&mut res);
                     match res.bytes().await {
                         Ok(bytes) => {
-                            // Parse the response
-                            if let Ok(response_json) = serde_json::from_slice::<Value>(&bytes) {
-                                // Extract and set output based on operation type
-                                let observation_output = match operation_type {
-                                    "chat" => {
-                                        // Chat completions: extract message from first choice
-                                        response_json
-                                            .get("choices")
-                                            .and_then(|choices| choices.as_array())
-                                            .and_then(|arr| arr.first())
-                                            .and_then(|choice| choice.get("message"))
-                                            .map(|message| {
-                                                json!({
-                                                    "choices": [{
-                                                        "message": message
-                                                    }]
-                                                })
-                                            })
-                                    }
-                                    "completion" => {
-                                        // Text completions: extract text from choices
-                                        response_json
-                                            .get("choices")
-                                            .and_then(|choices| choices.as_array())
-                                            .map(|choices_arr| {
-                                                let texts: Vec<_> = choices_arr
-                                                    .iter()
-                                                    .filter_map(|c| c.get("text"))
-                                                    .collect();
-                                                json!({
-                                                    "choices": texts
-                                                })
-                                            })
-                                    }
-                                    "embedding" => {
-                                        // Embeddings: extract embedding vectors
-                                        response_json
-                                            .get("data")
-                                            .and_then(|data| data.as_array())
-                                            .map(|data_arr| {
-                                                json!({
-                                                    "embeddings_count": data_arr.len(),
-                                                    // Don't include full vectors as they're too large
-                                                    "model": response_json.get("model")
-                                                })
-                                            })
-                                    }
-                                    "image" => {
-                                        // Image generation: extract URLs or b64_json
-                                        response_json
-                                            .get("data")
-                                            .and_then(|data| data.as_array())
-                                            .map(|data_arr| {
-                                                let urls: Vec<_> = data_arr
-                                                    .iter()
-                                                    .filter_map(|item| item.get("url"))
-                                                    .collect();
-                                                let b64_images_count = data_arr
-                                                    .iter()
-                                                    .filter(|item| item.get("b64_json").is_some())
-                                                    .count();
-                                                json!({
-                                                    "urls": urls,
-                                                    "b64_images_count": b64_images_count
-                                                })
-                                            })
-                                    }
-                                    _ => None,
-                                };
-
-                                // Set observation output if available
-                                if let Some(output) = observation_output {
-                                    span.set_attribute(KeyValue::new(
-                                        "langfuse.observation.output",
-                                        output.to_string(),
-                                    ));
-                                }
-
-                                // Set token usage on span (if available)
-                                if let Some(usage) = response_json.get("usage") {
-                                    if let Some(prompt_tokens) =
-                                        usage.get("prompt_tokens").and_then(|v| v.as_i64())
-                                    {
-                                        span.set_attribute(KeyValue::new(
-                                            GEN_AI_USAGE_INPUT_TOKENS,
-                                            prompt_tokens,
-                                        ));
-                                    }
-                                    if let Some(completion_tokens) =
-                                        usage.get("completion_tokens").and_then(|v| v.as_i64())
-                                    {
-                                        span.set_attribute(KeyValue::new(
-                                            GEN_AI_USAGE_OUTPUT_TOKENS,
-                                            completion_tokens,
-                                        ));
-                                    }
-                                    // Total tokens is not in semantic conventions, but useful for Langfuse
-                                    if let Some(total_tokens) =
-                                        usage.get("total_tokens").and_then(|v| v.as_i64())
-                                    {
-                                        span.set_attribute(KeyValue::new(
-                                            "langfuse.observation.usage.total",
-                                            total_tokens,
-                                        ));
-                                    }
-                                }
-                            }
-
-                            // Reconstruct the response with the buffered body
-                            let new_response = Response::from(
-                                http::Response::builder()
-                                    .status(status)
-                                    .body(bytes)
-                                    .unwrap(),
-                            );
-                            Ok(new_response)
+                            let error = ErrorResponse::parse(status, &bytes);
+                            outcome.set_error(config, &error.record(operation_type));
+                            Ok(head.into_response(bytes.into()))
                         }
                         Err(e) => {
-                            span.set_status(Status::error(format!(
-                                "Failed to read response body: {}",
-                                e
-                            )));
-                            span.set_attribute(KeyValue::new(ERROR_TYPE, e.to_string()));
-                            Err(reqwest_middleware::Error::Reqwest(e))
+                            outcome.set_error(
+                                config,
+                                &ErrorRecord::new(
+                                    operation_type,
+                                    &format!("HTTP {} (failed to read error body: {})", status, e),
+                                    &status.as_u16().to_string(),
+                                ),
+                            );
+                            Ok(head.into_response(reqwest::Body::from(Vec::new())))
                         }
                     }
-                } else {
-                    span.set_status(Status::error(format!("HTTP {}", status)));
-                    Ok(res)
                 }
             }
             Err(e) => {
-                span.set_status(Status::error(format!("Request failed: {}", e)));
-                span.set_attribute(KeyValue::new(ERROR_TYPE, e.to_string()));
+                outcome.set_error(
+                    config,
+                    &ErrorRecord::new(
+                        operation_type,
+                        &format!("Request failed: {}", e),
+                        request_error_type(&e),
+                    ),
+                );
                 Err(e)
             }
         };
 
-        // Record duration
-        let duration_ms = start_time.elapsed().as_millis() as i64;
-        span.set_attribute(KeyValue::new("duration_ms", duration_ms));
+        match attempt {
+            Some(attempt) => attempt.finish(&response),
+            None => {
+                generation
+                    .attempts()
+                    .record_untraced(start, outcome.attributes(), outcome.status())
+            }
+        }
+        generation.set_outcome(outcome);
+        response
+    }
+}
+
+/// Everything but the body of a response, kept aside while the body is consumed so the
+/// response handed back to the caller only differs in its (re-wrapped) body
+pub(crate) struct ResponseHead {
+    status: http::StatusCode,
+    version: http::Version,
+    url: reqwest::Url,
+    headers: http::HeaderMap,
+    extensions: Extensions,
+}
+
+impl ResponseHead {
+    pub(crate) fn take(res: &mut Response) -> Self {
+        Self {
+            status: res.status(),
+            version: res.version(),
+            url: res.url().clone(),
+            headers: std::mem::take(res.headers_mut()),
+            extensions: std::mem::take(res.extensions_mut()),
+        }
+    }
+
+    pub(crate) fn into_response(self, body: reqwest::Body) -> Response {
+        let mut response = http::Response::builder()
+            .status(self.status)
+            .version(self.version)
+            .url(self.url)
+            .body(body)
+            .expect("status and version of an existing response are valid");
+        *response.headers_mut() = self.headers;
+        response.extensions_mut().extend(self.extensions);
+        Response::from(response)
+    }
+}
+
+/// Record output and token usage from a buffered response body
+fn record_response_body(
+    span: &SpanRef<'_>,
+    metrics: &mut CallMetrics,
+    config: &OpenAITracingConfig,
+    operation_type: &str,
+    request_model: Option<&str>,
+    headers: &http::HeaderMap,
+    body: &[u8],
+) {
+    if operation_type == "speech" {
+        // Speech responses are audio: record its size, never its content
+        let content_type = headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok());
+        let output = config.record_content(
+            ContentKind::Output,
+            audio::speech_output(content_type, body.len()),
+        );
+        let response = ResponseRecord {
+            output: output.as_ref(),
+            audio_output_size: Some(body.len()),
+            ..ResponseRecord::new(operation_type)
+        };
+        for attribute in config.response_attributes(&response) {
+            span.set_attribute(attribute);
+        }
+        return;
+    }
+
+    match serde_json::from_slice::<Value>(body) {
+        Ok(response_json) => record_response_json(
+            span,
+            metrics,
+            config,
+            operation_type,
+            request_model,
+            &response_json,
+        ),
+        // Transcriptions requested as text, srt or vtt are returned as plain text
+        Err(_) if matches!(operation_type, "transcription" | "translation") => {
+            let text = String::from_utf8_lossy(body);
+            set_observation_output(span, config, operation_type, json!({ "text": text }));
+        }
+        Err(_) => {}
+    }
+}
 
-        span.end();
+/// Record the output of a response that carries nothing else, such as speech or a plain
+/// text transcription
+fn set_observation_output(
+    span: &SpanRef<'_>,
+    config: &OpenAITracingConfig,
+    operation_type: &str,
+    output: Value,
+) {
+    let output = config.record_content(ContentKind::Output, output);
+    let response = ResponseRecord {
+        output: output.as_ref(),
+        ..ResponseRecord::new(operation_type)
+    };
+    for attribute in config.response_attributes(&response) {
+        span.set_attribute(attribute);
+    }
+}
 
-        response
+/// Record output and token usage from a parsed (or stream-assembled) response body
+pub(crate) fn record_response_json(
+    span: &SpanRef<'_>,
+    metrics: &mut CallMetrics,
+    config: &OpenAITracingConfig,
+    operation_type: &str,
+    request_model: Option<&str>,
+    response_json: &Value,
+) {
+    // Extract and set output based on operation type
+    let observation_output = match operation_type {
+        "chat" => {
+            // Chat completions: extract every choice with its message and finish reason
+            crate::chat::extract_output(response_json)
+        }
+        "completion" => {
+            // Text completions: extract text from choices
+            response_json
+                .get("choices")
+                .and_then(|choices| choices.as_array())
+                .map(|choices_arr| {
+                    let texts: Vec<_> = choices_arr.iter().filter_map(|c| c.get("text")).collect();
+                    json!({
+                        "choices": texts
+                    })
+                })
+        }
+        "embedding" => {
+            // Embeddings: extract embedding vectors
+            response_json
+                .get("data")
+                .and_then(|data| data.as_array())
+                .map(|data_arr| {
+                    json!({
+                        "embeddings_count": data_arr.len(),
+                        // Don't include full vectors as they're too large
+                        "model": response_json.get("model")
+                    })
+                })
+        }
+        "response" => {
+            // Responses API: extract output items (messages, reasoning, function calls)
+            crate::responses::extract_output(response_json)
+        }
+        "transcription" | "translation" => {
+            // Audio to text: extract the text (and language/duration for verbose_json)
+            audio::transcription_output(response_json)
+        }
+        "image" => {
+            // Image generation: extract URLs or b64_json
+            response_json
+                .get("data")
+                .and_then(|data| data.as_array())
+                .map(|data_arr| {
+                    let urls: Vec<_> = data_arr.iter().filter_map(|item| item.get("url")).collect();
+                    let b64_images_count = data_arr
+                        .iter()
+                        .filter(|item| item.get("b64_json").is_some())
+                        .count();
+                    json!({
+                        "urls": urls,
+                        "b64_images_count": b64_images_count
+                    })
+                })
+        }
+        _ => None,
+    };
+
+    if operation_type == "chat" && config.message_events() {
+        for attributes in events::choice_events(config, response_json) {
+            span.add_event(events::GEN_AI_CHOICE, attributes);
+        }
+    }
+
+    // What the server actually did, which may differ from what was requested (e.g. a
+    // deployment pointing at a newer model version)
+    let response_model = response_json.get("model").and_then(|v| v.as_str());
+    let finish_reasons = match operation_type {
+        "response" => crate::responses::finish_reasons(response_json),
+        _ => crate::chat::finish_reasons(response_json),
+    };
+    let usage = response_json
+        .get("usage")
+        .filter(|u| u.is_object())
+        .map(Usage::parse);
+    let output =
+        observation_output.and_then(|output| config.record_content(ContentKind::Output, output));
+
+    // Price by the model that served the call, falling back to the requested model or
+    // deployment name (which may be an alias in the pricing table)
+    let models: Vec<&str> = [response_model, request_model]
+        .into_iter()
+        .flatten()
+        .collect();
+    let (cost, unpriced_model) = match (&config.pricing, &usage) {
+        (Some(pricing), Some(usage)) => match pricing.cost(&models, usage) {
+            Ok(cost) => (cost, None),
+            Err(model) => (None, Some(model)),
+        },
+        _ => (None, None),
+    };
+    let response = ResponseRecord {
+        model: response_model,
+        id: response_json.get("id").and_then(|v| v.as_str()),
+        finish_reasons: &finish_reasons,
+        output: output.as_ref(),
+        usage: usage.as_ref(),
+        cost: cost.as_ref(),
+        unpriced_model,
+        system_fingerprint: response_json
+            .get("system_fingerprint")
+            .and_then(|v| v.as_str()),
+        service_tier: response_json.get("service_tier").and_then(|v| v.as_str()),
+        ..ResponseRecord::new(operation_type)
+    };
+    for attribute in config.response_attributes(&response) {
+        span.set_attribute(attribute);
+    }
+
+    if let Some(model) = response_model {
+        metrics.set_response_model(model);
+    }
+
+    if let Some(usage) = &usage {
+        metrics.record_usage(usage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HttpClientWithMiddleware;
+    use crate::{LangfuseAttributes, LangfuseContext, LangfuseContextBuilder};
+    use async_openai::http_client::HttpClient;
+    use futures::StreamExt;
+    use opentelemetry::StringValue;
+    use opentelemetry_sdk::export::trace::SpanData;
+    use opentelemetry_sdk::testing::trace::{InMemorySpanExporter, InMemorySpanExporterBuilder};
+    use opentelemetry_sdk::trace::TracerProvider;
+    use opentelemetry_semantic_conventions::attribute::{
+        ERROR_TYPE, GEN_AI_OPENAI_RESPONSE_SERVICE_TIER, GEN_AI_OPERATION_NAME,
+        GEN_AI_REQUEST_MODEL, GEN_AI_RESPONSE_FINISH_REASONS, GEN_AI_RESPONSE_ID,
+        GEN_AI_RESPONSE_MODEL, GEN_AI_SYSTEM, GEN_AI_USAGE_INPUT_TOKENS,
+        GEN_AI_USAGE_OUTPUT_TOKENS, HTTP_REQUEST_RESEND_COUNT,
+    };
+    use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn traced_client() -> ClientWithMiddleware {
+        client_with(OpenAITracingMiddleware::new())
+    }
+
+    fn client_with(middleware: OpenAITracingMiddleware) -> ClientWithMiddleware {
+        ClientBuilder::new(reqwest::Client::new())
+            .with(middleware)
+            .build()
+    }
+
+    /// A builder exporting to an in-memory exporter, isolated from the global context
+    fn in_memory_builder() -> (OpenAITracingMiddlewareBuilder, InMemorySpanExporter) {
+        let exporter = InMemorySpanExporterBuilder::new().build();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let builder = OpenAITracingMiddleware::builder()
+            .with_tracer_provider(provider)
+            .with_context(LangfuseContext::new());
+        (builder, exporter)
+    }
+
+    fn find_span<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+        spans
+            .iter()
+            .find(|span| span.name == name)
+            .unwrap_or_else(|| panic!("no span named {}", name))
+    }
+
+    fn attribute(span: &SpanData, key: &str) -> Option<opentelemetry::Value> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    }
+
+    /// A metric reader that can be collected from after being handed to a provider
+    #[derive(Clone, Debug)]
+    struct SharedReader(Arc<opentelemetry_sdk::metrics::ManualReader>);
+
+    impl opentelemetry_sdk::metrics::reader::MetricReader for SharedReader {
+        fn register_pipeline(
+            &self,
+            pipeline: std::sync::Weak<opentelemetry_sdk::metrics::Pipeline>,
+        ) {
+            self.0.register_pipeline(pipeline)
+        }
+        fn collect(
+            &self,
+            rm: &mut opentelemetry_sdk::metrics::data::ResourceMetrics,
+        ) -> opentelemetry_sdk::metrics::MetricResult<()> {
+            self.0.collect(rm)
+        }
+        fn force_flush(&self) -> opentelemetry_sdk::metrics::MetricResult<()> {
+            self.0.force_flush()
+        }
+        fn shutdown(&self) -> opentelemetry_sdk::metrics::MetricResult<()> {
+            self.0.shutdown()
+        }
+        fn temporality(
+            &self,
+            kind: opentelemetry_sdk::metrics::InstrumentKind,
+        ) -> opentelemetry_sdk::metrics::Temporality {
+            self.0.temporality(kind)
+        }
+    }
+
+    fn in_memory_meter_provider() -> (opentelemetry_sdk::metrics::SdkMeterProvider, SharedReader) {
+        let reader = SharedReader(Arc::new(
+            opentelemetry_sdk::metrics::ManualReader::builder().build(),
+        ));
+        let provider = opentelemetry_sdk::metrics::SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        (provider, reader)
+    }
+
+    /// Attributes, count and sum of every data point of a histogram
+    fn histogram<T: Copy + 'static>(
+        reader: &SharedReader,
+        name: &str,
+    ) -> Vec<(Vec<KeyValue>, u64, T)> {
+        use opentelemetry_sdk::metrics::reader::MetricReader;
+        let mut metrics = opentelemetry_sdk::metrics::data::ResourceMetrics {
+            resource: opentelemetry_sdk::Resource::empty(),
+            scope_metrics: Vec::new(),
+        };
+        reader.collect(&mut metrics).unwrap();
+        metrics
+            .scope_metrics
+            .iter()
+            .flat_map(|scope| scope.metrics.iter())
+            .filter(|metric| metric.name == name)
+            .filter_map(|metric| {
+                metric
+                    .data
+                    .as_any()
+                    .downcast_ref::<opentelemetry_sdk::metrics::data::Histogram<T>>()
+            })
+            .flat_map(|histogram| histogram.data_points.iter())
+            .map(|point| (point.attributes.clone(), point.count, point.sum))
+            .collect()
+    }
+
+    fn chat_completion_body() -> Value {
+        json!({
+            "id": "chatcmpl-123",
+            "object": "chat.completion",
+            "model": "gpt-4o-mini-2024-07-18",
+            "system_fingerprint": "fp_44709d6fcb",
+            "service_tier": "default",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hello!"},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 9, "completion_tokens": 2, "total_tokens": 11}
+        })
+    }
+
+    async fn mock_chat_completion(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(chat_completion_body())
+                    .insert_header("x-request-id", "req-abc123")
+                    .insert_header("openai-processing-ms", "42")
+                    .insert_header("x-ratelimit-remaining-requests", "499")
+                    .insert_header("x-ratelimit-remaining-tokens", "149990"),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_buffered_response_preserves_headers() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let url = format!("{}/v1/chat/completions", server.uri());
+        let response = traced_client()
+            .post(&url)
+            .body(
+                json!({"model": "gpt-4o-mini", "messages": [{"role": "user", "content": "Hi"}]})
+                    .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.url().as_str(), url);
+        let headers = response.headers();
+        assert_eq!(headers["content-type"], "application/json");
+        assert_eq!(headers["x-request-id"], "req-abc123");
+        assert_eq!(headers["openai-processing-ms"], "42");
+        assert_eq!(headers["x-ratelimit-remaining-requests"], "499");
+        assert_eq!(headers["x-ratelimit-remaining-tokens"], "149990");
+
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body, chat_completion_body());
+    }
+
+    #[tokio::test]
+    async fn test_http_client_surfaces_headers() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let http_client = HttpClientWithMiddleware::new(traced_client());
+        let body = json!({"model": "gpt-4o-mini", "messages": [{"role": "user", "content": "Hi"}]});
+        let response = http_client
+            .request(
+                reqwest::Method::POST,
+                format!("{}/v1/chat/completions", server.uri())
+                    .parse()
+                    .unwrap(),
+                http::HeaderMap::new(),
+                Some(body.to_string().into()),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.headers["x-request-id"], "req-abc123");
+        assert_eq!(response.headers["x-ratelimit-remaining-requests"], "499");
+        assert_eq!(
+            serde_json::from_slice::<Value>(&response.body).unwrap(),
+            chat_completion_body()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streaming_response_passes_through() {
+        let server = MockServer::start().await;
+        let sse_body = concat!(
+            "data: {\"id\":\"chatcmpl-1\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"id\":\"chatcmpl-1\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(sse_body, "text/event-stream")
+                    .insert_header("x-request-id", "req-stream"),
+            )
+            .mount(&server)
+            .await;
+
+        let response = traced_client()
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "stream": true, "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        assert_eq!(response.headers()["x-request-id"], "req-stream");
+
+        let mut received = Vec::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            received.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(received, sse_body.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_spans_exported_to_injected_provider() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        // Keep the client (and with it the provider) alive: dropping the provider shuts
+        // down the in-memory exporter, which discards the finished spans
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.build());
+        let response = client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(
+                json!({"model": "gpt-4o-mini", "messages": [{"role": "user", "content": "Hi"}]})
+                    .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 2);
+
+        let root = find_span(&spans, "OpenAI-generation");
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert_eq!(generation.parent_span_id, root.span_context.span_id());
+        assert_eq!(
+            generation.span_context.trace_id(),
+            root.span_context.trace_id()
+        );
+
+        let scope = &generation.instrumentation_scope;
+        assert_eq!(scope.name(), crate::DEFAULT_TRACER_NAME);
+        assert_eq!(scope.version(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(
+            scope.schema_url(),
+            Some(opentelemetry_semantic_conventions::SCHEMA_URL)
+        );
+
+        assert_eq!(
+            attribute(generation, GEN_AI_REQUEST_MODEL),
+            Some("gpt-4o-mini".into())
+        );
+        assert_eq!(
+            attribute(generation, GEN_AI_USAGE_INPUT_TOKENS),
+            Some(9i64.into())
+        );
+        assert_eq!(
+            attribute(generation, GEN_AI_USAGE_OUTPUT_TOKENS),
+            Some(2i64.into())
+        );
+        assert_eq!(
+            attribute(generation, GEN_AI_RESPONSE_FINISH_REASONS),
+            Some(opentelemetry::Value::Array(
+                vec![StringValue::from("stop")].into()
+            ))
+        );
+        assert_eq!(
+            attribute(generation, GEN_AI_RESPONSE_MODEL),
+            Some("gpt-4o-mini-2024-07-18".into())
+        );
+        assert_eq!(
+            attribute(generation, GEN_AI_RESPONSE_ID),
+            Some("chatcmpl-123".into())
+        );
+        assert_eq!(
+            attribute(generation, "openai.response.system_fingerprint"),
+            Some("fp_44709d6fcb".into())
+        );
+        assert_eq!(
+            attribute(generation, GEN_AI_OPENAI_RESPONSE_SERVICE_TIER),
+            Some("default".into())
+        );
+        assert_eq!(
+            attribute(
+                generation,
+                "http.response.header.x-ratelimit-remaining-tokens"
+            ),
+            Some(opentelemetry::Value::Array(
+                vec![StringValue::from("149990")].into()
+            ))
+        );
+        assert_eq!(
+            attribute(generation, "http.response.header.x-request-id"),
+            Some(opentelemetry::Value::Array(
+                vec![StringValue::from("req-abc123")].into()
+            ))
+        );
+        assert_eq!(
+            attribute(generation, "http.response.header.content-type"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_injected_tracer_takes_precedence() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let exporter = InMemorySpanExporterBuilder::new().build();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer = opentelemetry::trace::TracerProvider::tracer(&provider, "explicit-tracer");
+        let middleware = OpenAITracingMiddleware::builder()
+            .with_tracer(tracer)
+            .with_root_span(false)
+            .with_context(LangfuseContext::new())
+            .build();
+
+        client_with(middleware)
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].instrumentation_scope.name(), "explicit-tracer");
+    }
+
+    #[tokio::test]
+    async fn test_redactors_of_middleware_and_context() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(
+            builder
+                .with_redactor(crate::RegexRedactor::pii())
+                .with_redactor(crate::JsonPathRedactor::new(["$.messages[*].name"]).unwrap())
+                .build(),
+        );
+        let context = LangfuseContextBuilder::new()
+            .redactor(|kind, content| match kind {
+                crate::ContentKind::Input => Some(content),
+                crate::ContentKind::Output => None,
+            })
+            .build();
+        client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .with_extension(context)
+            .body(
+                json!({"model": "gpt-4o-mini", "messages": [
+                    {"role": "user", "name": "jane", "content": "Mail me at jane@example.com"}
+                ]})
+                .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        let input: Value = serde_json::from_str(
+            &attribute(generation, "langfuse.observation.input")
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap();
+        assert_eq!(
+            input,
+            json!({"messages": [{"role": "user", "content": "Mail me at [EMAIL]"}]})
+        );
+        assert_eq!(attribute(generation, "langfuse.observation.output"), None);
+    }
+
+    #[tokio::test]
+    async fn test_openinference_convention_replaces_defaults() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(
+            builder
+                .with_attribute_conventions(vec![Arc::new(crate::OpenInferenceConvention)])
+                .build(),
+        );
+        client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(
+                json!({"model": "gpt-4o-mini", "messages": [{"role": "user", "content": "Hi"}]})
+                    .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert_eq!(
+            attribute(generation, "openinference.span.kind"),
+            Some("LLM".into())
+        );
+        assert_eq!(
+            attribute(generation, "llm.input_messages.0.message.content"),
+            Some("Hi".into())
+        );
+        assert_eq!(
+            attribute(generation, "llm.output_messages.0.message.content"),
+            Some("Hello!".into())
+        );
+        assert_eq!(
+            attribute(generation, "llm.token_count.total"),
+            Some(11i64.into())
+        );
+        assert!(!generation.attributes.iter().any(|kv| kv
+            .key
+            .as_str()
+            .starts_with("langfuse.observation.")
+            || kv.key.as_str() == GEN_AI_REQUEST_MODEL));
+    }
+
+    #[tokio::test]
+    async fn test_message_events() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.with_message_events(true).build());
+        client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(
+                json!({"model": "gpt-4o-mini", "messages": [
+                    {"role": "system", "content": "Be brief"},
+                    {"role": "user", "content": "Hi"}
+                ]})
+                .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        let names: Vec<_> = generation
+            .events
+            .iter()
+            .map(|event| event.name.as_ref())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "gen_ai.system.message",
+                "gen_ai.user.message",
+                "gen_ai.choice"
+            ]
+        );
+        let choice = &generation.events.events[2];
+        assert!(choice
+            .attributes
+            .contains(&KeyValue::new("message", "{\"content\":\"Hello!\"}")));
+        assert!(choice
+            .attributes
+            .contains(&KeyValue::new("finish_reason", "stop")));
+    }
+
+    #[tokio::test]
+    async fn test_inline_images_are_recorded_as_placeholders() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let stored = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = {
+            let stored = stored.clone();
+            move |media: &crate::MediaReference, data: &[u8]| {
+                stored.lock().unwrap().push(data.to_vec());
+                Some(format!("media://{}", media.sha256))
+            }
+        };
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.with_media_sink(sink).build());
+        client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(
+                json!({"model": "gpt-4o-mini", "messages": [{"role": "user", "content": [
+                    {"type": "text", "text": "What is this?"},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,aGVsbG8="}}
+                ]}]})
+                .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        let input: Value = serde_json::from_str(
+            &attribute(generation, "langfuse.observation.input")
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap();
+        let url = &input["messages"][0]["content"][1]["image_url"]["url"];
+        assert_eq!(url["content_type"], "image/png");
+        assert_eq!(url["size_bytes"], 5);
+        assert!(url["uri"].as_str().unwrap().starts_with("media://2cf24dba"));
+        assert_eq!(*stored.lock().unwrap(), vec![b"hello".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_recorded_with_injected_meter_provider() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let (meter_provider, reader) = in_memory_meter_provider();
+        let (builder, _exporter) = in_memory_builder();
+        let client = client_with(builder.with_meter_provider(meter_provider).build());
+        client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        let tokens = histogram::<u64>(&reader, "gen_ai.client.token.usage");
+        assert_eq!(tokens.len(), 2);
+        let input = tokens
+            .iter()
+            .find(|(attributes, _, _)| {
+                attributes.contains(&KeyValue::new("gen_ai.token.type", "input"))
+            })
+            .unwrap();
+        assert_eq!(input.2, 9);
+        for expected in [
+            KeyValue::new(GEN_AI_OPERATION_NAME, "chat"),
+            KeyValue::new(GEN_AI_SYSTEM, "openai"),
+            KeyValue::new(GEN_AI_REQUEST_MODEL, "gpt-4o-mini"),
+            KeyValue::new(GEN_AI_RESPONSE_MODEL, "gpt-4o-mini-2024-07-18"),
+            KeyValue::new("server.address", "127.0.0.1"),
+        ] {
+            assert!(input.0.contains(&expected), "{:?}", expected);
+        }
+
+        let durations = histogram::<f64>(&reader, "gen_ai.client.operation.duration");
+        assert_eq!(durations.len(), 1);
+        assert_eq!(durations[0].1, 1);
+        assert!(!durations[0]
+            .0
+            .iter()
+            .any(|kv| kv.key.as_str() == ERROR_TYPE));
+        // Only streamed calls have a first token
+        assert!(histogram::<f64>(&reader, "gen_ai.client.time_to_first_token").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_record_error_type() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(429).set_body_json(json!({"error": {
+                "message": "Rate limit reached",
+                "type": "requests",
+                "code": "rate_limit_exceeded"
+            }})))
+            .mount(&server)
+            .await;
+
+        let (meter_provider, reader) = in_memory_meter_provider();
+        let (builder, _exporter) = in_memory_builder();
+        let client = client_with(builder.with_meter_provider(meter_provider).build());
+        client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        let durations = histogram::<f64>(&reader, "gen_ai.client.operation.duration");
+        assert_eq!(durations.len(), 1);
+        assert!(durations[0]
+            .0
+            .contains(&KeyValue::new(ERROR_TYPE, "rate_limit_exceeded")));
+        assert!(histogram::<u64>(&reader, "gen_ai.client.token.usage").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_request_records_low_cardinality_error_type() {
+        // Nothing listens on a port once its listener is closed
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let uri = format!("http://{}", address);
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.build());
+        let result = client
+            .post(format!("{}/v1/chat/completions", uri))
+            .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+            .send()
+            .await;
+        assert!(result.is_err());
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert!(matches!(generation.status, Status::Error { .. }));
+        assert_eq!(attribute(generation, ERROR_TYPE), Some("connect".into()));
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_error_body_still_returns_response() {
+        use std::io::{Read, Write};
+
+        // A server that cuts the error body short of its announced length
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.ends_with(b"]}") {
+                let read = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 100\r\n\r\n{\"error\"",
+                )
+                .unwrap();
+        });
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.build());
+        let response = client
+            .post(format!("{}/v1/chat/completions", uri))
+            .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+        server.join().unwrap();
+        assert_eq!(response.status(), 503);
+        assert!(response.bytes().await.unwrap().is_empty());
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert!(matches!(
+            &generation.status,
+            Status::Error { description } if description.contains("failed to read error body")
+        ));
+        assert_eq!(attribute(generation, ERROR_TYPE), Some("503".into()));
+    }
+
+    #[tokio::test]
+    async fn test_streaming_span_ends_with_stream() {
+        let server = MockServer::start().await;
+        let sse_body = concat!(
+            "data: {\"id\":\"chatcmpl-1\",\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"id\":\"chatcmpl-1\",\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"id\":\"chatcmpl-1\",\"model\":\"gpt-4o-mini\",\"choices\":[],\"usage\":{\"prompt_tokens\":4,\"completion_tokens\":2,\"total_tokens\":6}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(sse_body, "text/event-stream"))
+            .mount(&server)
+            .await;
+
+        let (meter_provider, reader) = in_memory_meter_provider();
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.with_meter_provider(meter_provider).build());
+        let response = client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "stream": true, "stream_options": {"include_usage": true}, "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        // Nothing is ended until the caller has consumed the stream
+        assert!(exporter.get_finished_spans().unwrap().is_empty());
+
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            chunk.unwrap();
+        }
+        drop(stream);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 2);
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        let output: Value = serde_json::from_str(
+            &attribute(generation, "langfuse.observation.output")
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap();
+        assert_eq!(output["choices"][0]["message"]["content"], "Hello");
+        assert_eq!(
+            attribute(generation, "langfuse.observation.usage.total"),
+            Some(6i64.into())
+        );
+
+        let completion_start_time =
+            attribute(generation, "langfuse.observation.completion_start_time").unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(&completion_start_time.as_str()).is_ok());
+        assert!(attribute(generation, "openai.stream.time_to_first_token_ms").is_some());
+        assert_eq!(
+            attribute(generation, "openai.stream.output_chunks"),
+            Some(2i64.into())
+        );
+        assert_eq!(
+            generation
+                .events
+                .iter()
+                .filter(|event| event.name == "first_token")
+                .count(),
+            1
+        );
+
+        let first_token = histogram::<f64>(&reader, "gen_ai.client.time_to_first_token");
+        assert_eq!(first_token.len(), 1);
+        assert_eq!(first_token[0].1, 1);
+        let durations = histogram::<f64>(&reader, "gen_ai.client.operation.duration");
+        assert_eq!(durations[0].1, 1);
+        assert!(durations[0].2 >= first_token[0].2);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_stream_is_cancelled() {
+        let server = MockServer::start().await;
+        // The response is cut off before its finish reason and `[DONE]`
+        let sse_body = "data: {\"id\":\"chatcmpl-1\",\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"}}]}\n\n";
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(sse_body, "text/event-stream"))
+            .mount(&server)
+            .await;
+
+        let (meter_provider, reader) = in_memory_meter_provider();
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.with_meter_provider(meter_provider).build());
+        let response = client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "stream": true, "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        let mut stream = response.bytes_stream();
+        stream.next().await.unwrap().unwrap();
+        drop(stream);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert!(matches!(generation.status, Status::Error { .. }));
+        assert_eq!(
+            attribute(generation, "openai.stream.cancelled"),
+            Some(true.into())
+        );
+        assert_eq!(attribute(generation, ERROR_TYPE), Some("cancelled".into()));
+        assert_eq!(
+            attribute(generation, LangfuseAttributes::OBSERVATION_LEVEL),
+            Some("WARNING".into())
+        );
+        // The output received so far is still recorded
+        let output: Value = serde_json::from_str(
+            &attribute(generation, "langfuse.observation.output")
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap();
+        assert_eq!(output["choices"][0]["message"]["content"], "Hel");
+
+        let durations = histogram::<f64>(&reader, "gen_ai.client.operation.duration");
+        assert!(durations[0]
+            .0
+            .contains(&KeyValue::new(ERROR_TYPE, "cancelled")));
+    }
+
+    /// The generation span of a Responses API call streaming `events`, read to the end
+    async fn streamed_response_span(events: &[Value]) -> SpanData {
+        let server = MockServer::start().await;
+        let sse_body: String = events
+            .iter()
+            .map(|event| format!("data: {}\n\n", event))
+            .collect();
+        Mock::given(method("POST"))
+            .and(path("/v1/responses"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(sse_body, "text/event-stream"))
+            .mount(&server)
+            .await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.build());
+        let response = client
+            .post(format!("{}/v1/responses", server.uri()))
+            .body(json!({"model": "o4-mini", "stream": true, "input": "Hi"}).to_string())
+            .send()
+            .await
+            .unwrap();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            chunk.unwrap();
+        }
+        drop(stream);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        find_span(&spans, "OpenAI responses").clone()
+    }
+
+    #[tokio::test]
+    async fn test_failed_response_stream_records_error() {
+        let generation = streamed_response_span(&[
+            json!({"type": "response.created", "response": {"id": "resp_1", "status": "in_progress"}}),
+            json!({"type": "response.failed", "response": {
+                "id": "resp_1",
+                "status": "failed",
+                "error": {"code": "server_error", "message": "The model failed"}
+            }}),
+        ])
+        .await;
+
+        assert_eq!(
+            generation.status,
+            Status::error("Response failed: The model failed")
+        );
+        assert_eq!(
+            attribute(&generation, ERROR_TYPE),
+            Some("server_error".into())
+        );
+        assert_eq!(
+            attribute(&generation, LangfuseAttributes::OBSERVATION_LEVEL),
+            Some("ERROR".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_incomplete_response_stream_records_reason() {
+        let generation = streamed_response_span(&[
+            json!({"type": "response.output_text.delta", "delta": "Par"}),
+            json!({"type": "response.incomplete", "response": {
+                "id": "resp_1",
+                "status": "incomplete",
+                "incomplete_details": {"reason": "max_output_tokens"}
+            }}),
+        ])
+        .await;
+
+        assert_eq!(
+            generation.status,
+            Status::error("Response incomplete: max_output_tokens")
+        );
+        assert_eq!(
+            attribute(&generation, ERROR_TYPE),
+            Some("max_output_tokens".into())
+        );
+        // The partial output is still recorded
+        let output: Value = serde_json::from_str(
+            &attribute(&generation, "langfuse.observation.output")
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap();
+        assert_eq!(output["output"][0]["content"], "Par");
+    }
+
+    #[tokio::test]
+    async fn test_stream_dropped_after_done_is_completed() {
+        let server = MockServer::start().await;
+        let sse_body = concat!(
+            "data: {\"id\":\"chatcmpl-1\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(sse_body, "text/event-stream"))
+            .mount(&server)
+            .await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.build());
+        let response = client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "stream": true, "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        // Like most clients, stop reading at `[DONE]` without polling the end of the body
+        let mut stream = response.bytes_stream();
+        let mut received = Vec::new();
+        while !received.ends_with(b"data: [DONE]\n\n") {
+            received.extend_from_slice(&stream.next().await.unwrap().unwrap());
+        }
+        drop(stream);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert_eq!(generation.status, Status::Ok);
+        assert_eq!(attribute(generation, "openai.stream.cancelled"), None);
+    }
+
+    #[tokio::test]
+    async fn test_scoped_contexts_do_not_bleed_between_requests() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.build());
+        let request = |user: &'static str| {
+            let request = client
+                .post(format!("{}/v1/chat/completions", server.uri()))
+                .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+                .send();
+            LangfuseContextBuilder::new()
+                .user_id(user)
+                .session_id(format!("session-{}", user))
+                .build()
+                .scope(request)
+        };
+
+        let (a, b) = tokio::join!(request("user-a"), request("user-b"));
+        a.unwrap();
+        b.unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generations: Vec<_> = spans
+            .iter()
+            .filter(|span| span.name == "OpenAI chat.completions")
+            .collect();
+        assert_eq!(generations.len(), 2);
+        for span in generations {
+            let user = attribute(span, LangfuseAttributes::TRACE_USER_ID).unwrap();
+            let session = attribute(span, LangfuseAttributes::TRACE_SESSION_ID).unwrap();
+            assert_eq!(session.as_str(), format!("session-{}", user.as_str()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_trace_attributes_from_extensions() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let (builder, exporter) = in_memory_builder();
+        let context = LangfuseContextBuilder::new()
+            .user_id("context-user")
+            .session_id("context-session")
+            .build();
+        let client = client_with(builder.with_context(context).build());
+        let http_client = HttpClientWithMiddleware::new(client).with_trace_attributes(
+            RequestTraceAttributes::new()
+                .with_name("summarize")
+                .with_user_id("request-user")
+                .with_prompt("summarize-prompt", Some("3".to_string())),
+        );
+
+        http_client
+            .request(
+                reqwest::Method::POST,
+                format!("{}/v1/chat/completions", server.uri())
+                    .parse()
+                    .unwrap(),
+                http::HeaderMap::new(),
+                Some(
+                    json!({"model": "gpt-4o-mini", "messages": []})
+                        .to_string()
+                        .into(),
+                ),
+            )
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let root = find_span(&spans, "summarize");
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        for span in [root, generation] {
+            assert_eq!(
+                attribute(span, LangfuseAttributes::TRACE_USER_ID),
+                Some("request-user".into())
+            );
+            assert_eq!(
+                attribute(span, LangfuseAttributes::TRACE_SESSION_ID),
+                Some("context-session".into())
+            );
+        }
+        assert_eq!(
+            attribute(generation, LangfuseAttributes::OBSERVATION_PROMPT_NAME),
+            Some("summarize-prompt".into())
+        );
+        assert_eq!(
+            attribute(generation, LangfuseAttributes::OBSERVATION_PROMPT_VERSION),
+            Some("3".into())
+        );
+        assert_eq!(
+            attribute(root, LangfuseAttributes::OBSERVATION_PROMPT_NAME),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_langfuse_context_extension_replaces_context() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(
+            builder
+                .with_context(LangfuseContextBuilder::new().user_id("shared").build())
+                .build(),
+        );
+        client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .with_extension(LangfuseContextBuilder::new().user_id("one-off").build())
+            .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert_eq!(
+            attribute(generation, LangfuseAttributes::TRACE_USER_ID),
+            Some("one-off".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_responses_api_span() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/responses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "resp_123",
+                "object": "response",
+                "model": "o4-mini",
+                "output": [
+                    {"type": "reasoning", "summary": []},
+                    {"type": "message", "role": "assistant", "content": [{"type": "output_text", "text": "Paris"}]}
+                ],
+                "usage": {
+                    "input_tokens": 12,
+                    "output_tokens": 70,
+                    "total_tokens": 82,
+                    "output_tokens_details": {"reasoning_tokens": 64}
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.with_pricing(crate::PricingTable::bundled()).build());
+        client
+            .post(format!("{}/v1/responses", server.uri()))
+            .body(json!({"model": "o4-mini", "instructions": "Answer tersely", "input": "Capital of France?"}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI responses");
+        assert_eq!(
+            attribute(generation, GEN_AI_OPERATION_NAME),
+            Some("response".into())
+        );
+        assert_eq!(
+            attribute(generation, "langfuse.observation.input"),
+            Some(
+                json!({"instructions": "Answer tersely", "input": "Capital of France?"})
+                    .to_string()
+                    .into()
+            )
+        );
+        let output: Value = serde_json::from_str(
+            &attribute(generation, "langfuse.observation.output")
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap();
+        assert_eq!(output["output"][1]["content"], "Paris");
+        assert_eq!(
+            attribute(generation, GEN_AI_USAGE_INPUT_TOKENS),
+            Some(12i64.into())
+        );
+        assert_eq!(
+            attribute(generation, GEN_AI_USAGE_OUTPUT_TOKENS),
+            Some(70i64.into())
+        );
+        let usage_details: Value = serde_json::from_str(
+            &attribute(generation, LangfuseAttributes::OBSERVATION_USAGE_DETAILS)
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap();
+        assert_eq!(usage_details["output_reasoning_tokens"], 64);
+        assert_eq!(usage_details["output"], 6);
+        let cost = attribute(generation, crate::pricing::GEN_AI_USAGE_COST).unwrap();
+        let expected = (12.0 * 1.1 + 70.0 * 4.4) / 1_000_000.0;
+        assert!(matches!(cost, opentelemetry::Value::F64(c) if (c - expected).abs() < 1e-12));
+    }
+
+    #[tokio::test]
+    async fn test_error_body_recorded_and_preserved() {
+        let server = MockServer::start().await;
+        let error_body = json!({"error": {
+            "message": "This model's maximum context length is 128000 tokens.",
+            "type": "invalid_request_error",
+            "param": "messages",
+            "code": "context_length_exceeded"
+        }});
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .set_body_json(error_body.clone())
+                    .insert_header("x-request-id", "req-error"),
+            )
+            .mount(&server)
+            .await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.build());
+        let response = client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o", "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 400);
+        assert_eq!(response.headers()["x-request-id"], "req-error");
+        assert_eq!(response.json::<Value>().await.unwrap(), error_body);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert_eq!(
+            generation.status,
+            Status::error(
+                "HTTP 400 Bad Request: This model's maximum context length is 128000 tokens."
+            )
+        );
+        assert_eq!(
+            attribute(generation, ERROR_TYPE),
+            Some("context_length_exceeded".into())
+        );
+        assert_eq!(
+            attribute(generation, LangfuseAttributes::OBSERVATION_LEVEL),
+            Some("ERROR".into())
+        );
+        assert_eq!(
+            attribute(generation, LangfuseAttributes::OBSERVATION_STATUS_MESSAGE),
+            Some(
+                "HTTP 400 Bad Request: This model's maximum context length is 128000 tokens."
+                    .into()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_conventions_without_langfuse_record_no_langfuse_attributes() {
+        let priced = MockServer::start().await;
+        mock_chat_completion(&priced).await;
+        let failing = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(429).set_body_json(json!({"error": {
+                "message": "Rate limit reached",
+                "type": "requests",
+                "code": "rate_limit_exceeded"
+            }})))
+            .mount(&failing)
+            .await;
+        let streaming = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                "data: {\"id\":\"chatcmpl-1\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+                "text/event-stream",
+            ))
+            .mount(&streaming)
+            .await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(
+            builder
+                .with_attribute_conventions(vec![
+                    Arc::new(crate::OpenInferenceConvention),
+                    Arc::new(crate::OpenLlmetryConvention),
+                ])
+                .with_pricing(crate::PricingTable::bundled())
+                .build(),
+        );
+        for server in [&priced, &failing] {
+            client
+                .post(format!("{}/v1/chat/completions", server.uri()))
+                .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+                .with_extension(
+                    RequestTraceAttributes::new()
+                        .with_session_id("session-1")
+                        .with_tags(vec!["eval".to_string()])
+                        .with_prompt("greeting", Some("2".to_string())),
+                )
+                .send()
+                .await
+                .unwrap();
+        }
+        // A stream with output that is dropped before it completes
+        let response = client
+            .post(format!("{}/v1/chat/completions", streaming.uri()))
+            .body(json!({"model": "gpt-4o-mini", "stream": true, "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+        let mut stream = response.bytes_stream();
+        stream.next().await.unwrap().unwrap();
+        drop(stream);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generations: Vec<_> = spans
+            .iter()
+            .filter(|span| span.name == "OpenAI chat.completions")
+            .collect();
+        assert_eq!(generations.len(), 3);
+        assert!(attribute(generations[0], "llm.cost.total").is_some());
+        assert_eq!(
+            attribute(generations[1], ERROR_TYPE),
+            Some("rate_limit_exceeded".into())
+        );
+        assert_eq!(
+            attribute(generations[2], ERROR_TYPE),
+            Some("cancelled".into())
+        );
+        // Trace attributes are named by the conventions too, on the root span as well
+        let root = spans
+            .iter()
+            .find(|span| span.name == "OpenAI-generation")
+            .unwrap();
+        for span in [root, generations[0]] {
+            assert_eq!(attribute(span, "session.id"), Some("session-1".into()));
+            assert_eq!(
+                attribute(span, "traceloop.association.properties.session_id"),
+                Some("session-1".into())
+            );
+            assert_eq!(
+                attribute(span, "tag.tags"),
+                Some(opentelemetry::Value::Array(
+                    vec![StringValue::from("eval")].into()
+                ))
+            );
+        }
+        for span in &spans {
+            for kv in &span.attributes {
+                let key = kv.key.as_str();
+                assert!(
+                    !key.starts_with("langfuse.")
+                        && !key.starts_with("openai.")
+                        && !key.starts_with("http.response.header.")
+                        && key != "duration_ms",
+                    "{} on {}",
+                    key,
+                    span.name
+                );
+            }
+        }
+    }
+
+    fn retry_policy() -> reqwest_retry::policies::ExponentialBackoff {
+        reqwest_retry::policies::ExponentialBackoff::builder()
+            .retry_bounds(
+                std::time::Duration::from_millis(1),
+                std::time::Duration::from_millis(5),
+            )
+            .build_with_max_retries(2)
+    }
+
+    /// A chat completion that fails with a 500 once before succeeding
+    async fn mock_flaky_chat_completion(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(server)
+            .await;
+        mock_chat_completion(server).await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_attempts_are_child_spans() {
+        let server = MockServer::start().await;
+        mock_flaky_chat_completion(&server).await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(builder.build())
+            .with(reqwest_retry::RetryTransientMiddleware::new_with_policy(
+                retry_policy(),
+            ))
+            .with(crate::OpenAIAttemptTracingMiddleware::new())
+            .build();
+        let response = client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generations: Vec<_> = spans
+            .iter()
+            .filter(|span| span.name == "OpenAI chat.completions")
+            .collect();
+        assert_eq!(generations.len(), 1);
+        let generation = generations[0];
+        assert_eq!(
+            attribute(generation, crate::attempts::OPENAI_REQUEST_ATTEMPTS),
+            Some(2i64.into())
+        );
+        assert_eq!(
+            attribute(generation, GEN_AI_USAGE_INPUT_TOKENS),
+            Some(9i64.into())
+        );
+
+        let attempts: Vec<_> = spans.iter().filter(|span| span.name == "attempt").collect();
+        assert_eq!(attempts.len(), 2);
+        for attempt in &attempts {
+            assert_eq!(attempt.parent_span_id, generation.span_context.span_id());
+        }
+        let first = attempts
+            .iter()
+            .find(|span| attribute(span, HTTP_REQUEST_RESEND_COUNT).is_none())
+            .unwrap();
+        let retry = attempts
+            .iter()
+            .find(|span| attribute(span, HTTP_REQUEST_RESEND_COUNT) == Some(1i64.into()))
+            .unwrap();
+        assert_eq!(
+            attribute(first, HTTP_RESPONSE_STATUS_CODE),
+            Some(500i64.into())
+        );
+        assert_eq!(
+            first.status,
+            Status::error("HTTP 500 Internal Server Error")
+        );
+        assert_eq!(
+            attribute(retry, HTTP_RESPONSE_STATUS_CODE),
+            Some(200i64.into())
+        );
+        assert!(attribute(retry, crate::attempts::OPENAI_ATTEMPT_BACKOFF_MS).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_outer_retry_records_attempts_of_one_generation() {
+        let server = MockServer::start().await;
+        mock_flaky_chat_completion(&server).await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(crate::OpenAIRetryScopeMiddleware::new())
+            .with(reqwest_retry::RetryTransientMiddleware::new_with_policy(
+                retry_policy(),
+            ))
+            .with(builder.build())
+            .build();
+        client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 4);
+        let root = find_span(&spans, "OpenAI-generation");
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert_eq!(generation.parent_span_id, root.span_context.span_id());
+
+        // The generation records the final response only, not the error of the first attempt
+        assert_eq!(generation.status, Status::Ok);
+        assert_eq!(attribute(generation, ERROR_TYPE), None);
+        assert_eq!(
+            attribute(generation, HTTP_RESPONSE_STATUS_CODE),
+            Some(200i64.into())
+        );
+        assert_eq!(
+            attribute(generation, crate::attempts::OPENAI_REQUEST_ATTEMPTS),
+            Some(2i64.into())
+        );
+        assert_eq!(
+            attribute(generation, GEN_AI_USAGE_INPUT_TOKENS),
+            Some(9i64.into())
+        );
+
+        let mut attempts: Vec<_> = spans.iter().filter(|span| span.name == "attempt").collect();
+        attempts.sort_by_key(|span| span.start_time);
+        assert_eq!(attempts.len(), 2);
+        for attempt in &attempts {
+            assert_eq!(attempt.parent_span_id, generation.span_context.span_id());
+            assert!(attempt.end_time <= generation.end_time);
+        }
+        assert_eq!(
+            attribute(attempts[0], crate::attempts::OPENAI_ATTEMPT_NUMBER),
+            Some(1i64.into())
+        );
+        assert_eq!(
+            attribute(attempts[0], HTTP_RESPONSE_STATUS_CODE),
+            Some(500i64.into())
+        );
+        assert_eq!(attribute(attempts[0], ERROR_TYPE), Some("500".into()));
+        assert!(matches!(attempts[0].status, Status::Error { .. }));
+        assert_eq!(
+            attribute(attempts[1], HTTP_REQUEST_RESEND_COUNT),
+            Some(1i64.into())
+        );
+        assert_eq!(attempts[1].status, Status::Ok);
+        assert!(attribute(attempts[1], crate::attempts::OPENAI_ATTEMPT_BACKOFF_MS).is_some());
+
+        // The root ends after the last attempt
+        assert!(root.end_time >= generation.end_time);
+    }
+
+    #[tokio::test]
+    async fn test_requests_sharing_extensions_are_separate_generations() {
+        let server = MockServer::start().await;
+        mock_chat_completion(&server).await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.build());
+        let mut extensions = Extensions::new();
+        for _ in 0..2 {
+            let request = client
+                .post(format!("{}/v1/chat/completions", server.uri()))
+                .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+                .build()
+                .unwrap();
+            client
+                .execute_with_extensions(request, &mut extensions)
+                .await
+                .unwrap();
+        }
+
+        // Both calls ended, each in a trace of its own, without being taken for attempts
+        let spans = exporter.get_finished_spans().unwrap();
+        let generations: Vec<_> = spans
+            .iter()
+            .filter(|span| span.name == "OpenAI chat.completions")
+            .collect();
+        assert_eq!(generations.len(), 2);
+        assert_ne!(
+            generations[0].span_context.trace_id(),
+            generations[1].span_context.trace_id()
+        );
+        assert!(spans.iter().all(|span| span.name != "attempt"));
+        for generation in generations {
+            assert_eq!(
+                attribute(generation, crate::attempts::OPENAI_REQUEST_ATTEMPTS),
+                None
+            );
+        }
+    }
+
+    fn retry_events(span: &SpanData) -> Vec<&opentelemetry::trace::Event> {
+        span.events
+            .iter()
+            .filter(|event| event.name == "openai.retry")
+            .collect()
+    }
+
+    fn event_attribute(
+        event: &opentelemetry::trace::Event,
+        key: &str,
+    ) -> Option<opentelemetry::Value> {
+        event
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    }
+
+    #[tokio::test]
+    async fn test_openai_retry_honours_retry_after() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after-ms", "20")
+                    .set_body_json(json!({"error": {
+                        "message": "Rate limit reached",
+                        "type": "requests",
+                        "code": "rate_limit_exceeded"
+                    }})),
+            )
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        mock_chat_completion(&server).await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(builder.build())
+            .with(crate::OpenAIRetryMiddleware::new())
+            .build();
+        let response = client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert_eq!(
+            attribute(generation, crate::attempts::OPENAI_REQUEST_ATTEMPTS),
+            Some(2i64.into())
+        );
+        let first = spans
+            .iter()
+            .find(|span| {
+                span.name == "attempt"
+                    && attribute(span, HTTP_RESPONSE_STATUS_CODE) == Some(429i64.into())
+            })
+            .unwrap();
+        let events = retry_events(first);
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            event_attribute(events[0], "openai.retry.decision"),
+            Some("retry".into())
+        );
+        assert_eq!(
+            event_attribute(events[0], "openai.retry.reason"),
+            Some("rate_limit_exceeded".into())
+        );
+        assert_eq!(
+            event_attribute(events[0], "openai.retry.delay_ms"),
+            Some(20i64.into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_openai_retry_gives_up_on_terminal_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(429).set_body_json(json!({"error": {
+                "message": "You exceeded your current quota",
+                "type": "insufficient_quota",
+                "code": "insufficient_quota"
+            }})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(builder.build())
+            .with(crate::OpenAIRetryMiddleware::new())
+            .build();
+        let response = client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(json!({"model": "gpt-4o-mini", "messages": []}).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 429);
+        // The error body is still readable by the caller
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "insufficient_quota");
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI chat.completions");
+        assert_eq!(
+            attribute(generation, crate::errors::OPENAI_ERROR_CODE),
+            Some("insufficient_quota".into())
+        );
+        let events = retry_events(find_span(&spans, "attempt"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            event_attribute(events[0], "openai.retry.decision"),
+            Some("give_up".into())
+        );
+        assert_eq!(
+            event_attribute(events[0], "openai.retry.give_up_reason"),
+            Some("not retryable".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_openai_retry_gives_up_on_streaming_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(builder.build())
+            .with(crate::OpenAIRetryMiddleware::new())
+            .build();
+        // A body streamed from elsewhere can't be sent a second time
+        let body = json!({"model": "gpt-4o-mini", "messages": []}).to_string();
+        let chunks = futures::stream::iter([Ok::<_, std::io::Error>(body)]);
+        let response = client
+            .post(format!("{}/v1/chat/completions", server.uri()))
+            .body(reqwest::Body::wrap_stream(chunks))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 503);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let events = retry_events(find_span(&spans, "attempt"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            event_attribute(events[0], "openai.retry.decision"),
+            Some("give_up".into())
+        );
+        assert_eq!(
+            event_attribute(events[0], "openai.retry.give_up_reason"),
+            Some("request body not cloneable".into())
+        );
+        assert_eq!(event_attribute(events[0], "openai.retry.delay_ms"), None);
+    }
+
+    #[tokio::test]
+    async fn test_transcription_span_records_form_not_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/audio/transcriptions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"text": "Hallo"})))
+            .mount(&server)
+            .await;
+
+        let mut body = Vec::new();
+        body.extend_from_slice(
+            b"--B\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n",
+        );
+        body.extend_from_slice(
+            b"--B\r\nContent-Disposition: form-data; name=\"language\"\r\n\r\nnl\r\n",
+        );
+        body.extend_from_slice(b"--B\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.mp3\"\r\nContent-Type: audio/mpeg\r\n\r\n");
+        body.extend_from_slice(&[7u8; 100]);
+        body.extend_from_slice(b"\r\n--B--\r\n");
+
+        let (builder, exporter) = in_memory_builder();
+        let http_client = HttpClientWithMiddleware::new(client_with(builder.build()));
+        http_client
+            .request_multipart(
+                reqwest::Method::POST,
+                format!("{}/v1/audio/transcriptions", server.uri())
+                    .parse()
+                    .unwrap(),
+                http::HeaderMap::new(),
+                async_openai::http_client::MultipartForm {
+                    boundary: "B".to_string(),
+                    body: body.into(),
+                },
+            )
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI audio.transcriptions");
+        assert_eq!(
+            attribute(generation, GEN_AI_REQUEST_MODEL),
+            Some("whisper-1".into())
+        );
+        assert_eq!(
+            attribute(generation, audio::AUDIO_LANGUAGE),
+            Some("nl".into())
+        );
+        assert_eq!(
+            attribute(generation, audio::AUDIO_FILE_SIZE),
+            Some(100i64.into())
+        );
+        let input: Value = serde_json::from_str(
+            &attribute(generation, "langfuse.observation.input")
+                .unwrap()
+                .as_str(),
+        )
+        .unwrap();
+        assert_eq!(input["file"]["filename"], "a.mp3");
+        assert_eq!(
+            attribute(generation, "langfuse.observation.output"),
+            Some(json!({"text": "Hallo"}).to_string().into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_speech_span_records_output_size() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/audio/speech"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(vec![1u8; 512], "audio/mpeg"))
+            .mount(&server)
+            .await;
+
+        let (builder, exporter) = in_memory_builder();
+        let client = client_with(builder.build());
+        let response = client
+            .post(format!("{}/v1/audio/speech", server.uri()))
+            .body(json!({"model": "tts-1", "input": "Hello", "voice": "alloy"}).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.bytes().await.unwrap().len(), 512);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let generation = find_span(&spans, "OpenAI audio.speech");
+        assert_eq!(
+            attribute(generation, audio::AUDIO_VOICE),
+            Some("alloy".into())
+        );
+        assert_eq!(
+            attribute(generation, audio::AUDIO_OUTPUT_SIZE),
+            Some(512i64.into())
+        );
+        assert_eq!(
+            attribute(generation, "langfuse.observation.output"),
+            Some(
+                json!({"audio_bytes": 512, "content_type": "audio/mpeg"})
+                    .to_string()
+                    .into()
+            )
+        );
     }
 }
@@ -0,0 +1,164 @@
+//! Minimal `multipart/form-data` parsing for tracing audio uploads
+//!
+//! Only what is needed for span attributes is kept: the text fields and, for file parts,
+//! the file name, content type and size. File contents are never retained. A form is
+//! parsed in a single pass over its body, searching for the boundaries with `memchr`.
+
+use memchr::memmem;
+
+/// A file part of a multipart form
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MultipartFile {
+    pub(crate) field: String,
+    pub(crate) filename: Option<String>,
+    pub(crate) content_type: Option<String>,
+    pub(crate) size: usize,
+}
+
+/// Summary of a multipart form: its text fields and file metadata
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct MultipartSummary {
+    pub(crate) fields: Vec<(String, String)>,
+    pub(crate) files: Vec<MultipartFile>,
+}
+
+impl MultipartSummary {
+    /// Parse a form body encoded with the given boundary
+    pub(crate) fn parse(body: &[u8], boundary: &str) -> Option<Self> {
+        let delimiter = format!("--{}", boundary);
+        let finder = memmem::Finder::new(delimiter.as_bytes());
+
+        let mut summary = MultipartSummary::default();
+        let mut rest = &body[finder.find(body)? + delimiter.len()..];
+        loop {
+            // The closing delimiter is followed by "--"
+            if rest.starts_with(b"--") {
+                break;
+            }
+            let next = finder.find(rest)?;
+            let part = strip_crlf(&rest[..next]);
+            summary.add_part(part);
+            rest = &rest[next + delimiter.len()..];
+        }
+        Some(summary)
+    }
+
+    /// Parse a form body using the boundary from its `Content-Type` header value
+    pub(crate) fn parse_with_content_type(body: &[u8], content_type: &str) -> Option<Self> {
+        let boundary = content_type.split(';').find_map(|param| {
+            param
+                .trim()
+                .strip_prefix("boundary=")
+                .map(|b| b.trim_matches('"'))
+        })?;
+        Self::parse(body, boundary)
+    }
+
+    /// The value of a text field
+    pub(crate) fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn add_part(&mut self, part: &[u8]) {
+        // Part headers come first, so only they are searched, not the file content
+        let Some(header_end) = memmem::find(part, b"\r\n\r\n") else {
+            return;
+        };
+        let headers = String::from_utf8_lossy(&part[..header_end]);
+        let content = &part[header_end + 4..];
+
+        let mut name = None;
+        let mut filename = None;
+        let mut content_type = None;
+        for line in headers.lines() {
+            let Some((header, value)) = line.split_once(':') else {
+                continue;
+            };
+            if header.eq_ignore_ascii_case("content-disposition") {
+                name = disposition_param(value, "name");
+                filename = disposition_param(value, "filename");
+            } else if header.eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.trim().to_string());
+            }
+        }
+
+        let Some(name) = name else {
+            return;
+        };
+        if filename.is_some() || content_type.is_some() {
+            self.files.push(MultipartFile {
+                field: name,
+                filename,
+                content_type,
+                size: content.len(),
+            });
+        } else {
+            self.fields
+                .push((name, String::from_utf8_lossy(content).into_owned()));
+        }
+    }
+}
+
+fn disposition_param(value: &str, param: &str) -> Option<String> {
+    value.split(';').find_map(|p| {
+        let (key, value) = p.trim().split_once('=')?;
+        (key == param).then(|| value.trim_matches('"').to_string())
+    })
+}
+
+fn strip_crlf(part: &[u8]) -> &[u8] {
+    let part = part.strip_prefix(b"\r\n").unwrap_or(part);
+    part.strip_suffix(b"\r\n").unwrap_or(part)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn form_body() -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(
+            b"--XYZ\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n",
+        );
+        body.extend_from_slice(
+            b"--XYZ\r\nContent-Disposition: form-data; name=\"language\"\r\n\r\nnl\r\n",
+        );
+        body.extend_from_slice(b"--XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"audio.mp3\"\r\nContent-Type: audio/mpeg\r\n\r\n");
+        body.extend_from_slice(&[0u8, 1, 2, 3, 13, 10, 45, 45]);
+        body.extend_from_slice(b"\r\n--XYZ--\r\n");
+        body
+    }
+
+    #[test]
+    fn test_parse_fields_and_files() {
+        let summary = MultipartSummary::parse_with_content_type(
+            &form_body(),
+            "multipart/form-data; boundary=XYZ",
+        )
+        .unwrap();
+
+        assert_eq!(summary.field("model"), Some("whisper-1"));
+        assert_eq!(summary.field("language"), Some("nl"));
+        assert_eq!(
+            summary.files,
+            vec![MultipartFile {
+                field: "file".to_string(),
+                filename: Some("audio.mp3".to_string()),
+                content_type: Some("audio/mpeg".to_string()),
+                size: 8,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_missing_boundary() {
+        assert_eq!(
+            MultipartSummary::parse_with_content_type(&form_body(), "multipart/form-data"),
+            None
+        );
+        assert_eq!(MultipartSummary::parse(b"not a form", "XYZ"), None);
+    }
+}
@@ -0,0 +1,208 @@
+//! Extraction of model parameters from request bodies
+//!
+//! Every [attribute convention](crate::AttributeConvention) records them its own way: all
+//! of them as a map (e.g. the Langfuse model parameters), or those with a semantic
+//! convention as the corresponding `gen_ai.request.*` attribute.
+
+use opentelemetry::{KeyValue, StringValue};
+use opentelemetry_semantic_conventions::attribute::{
+    GEN_AI_OPENAI_REQUEST_RESPONSE_FORMAT, GEN_AI_OPENAI_REQUEST_SEED,
+    GEN_AI_OPENAI_REQUEST_SERVICE_TIER, GEN_AI_REQUEST_FREQUENCY_PENALTY,
+    GEN_AI_REQUEST_MAX_TOKENS, GEN_AI_REQUEST_PRESENCE_PENALTY, GEN_AI_REQUEST_STOP_SEQUENCES,
+    GEN_AI_REQUEST_TEMPERATURE, GEN_AI_REQUEST_TOP_P,
+};
+use serde_json::{Map, Value};
+
+/// Top-level request fields recorded as model parameters
+const PARAMETERS: [&str; 17] = [
+    "temperature",
+    "top_p",
+    "max_tokens",
+    "max_completion_tokens",
+    "max_output_tokens",
+    "presence_penalty",
+    "frequency_penalty",
+    "seed",
+    "stop",
+    "n",
+    "response_format",
+    "tool_choice",
+    "parallel_tool_calls",
+    "reasoning_effort",
+    "service_tier",
+    "logprobs",
+    "top_logprobs",
+];
+
+/// Model parameters of a request
+pub(crate) fn extract_model_parameters(request: &Value) -> Map<String, Value> {
+    let mut parameters = Map::new();
+    for key in PARAMETERS {
+        if let Some(value) = request.get(key).filter(|v| !v.is_null()) {
+            parameters.insert(key.to_string(), value.clone());
+        }
+    }
+    // The Responses API nests the reasoning effort and output format
+    if let Some(effort) = request
+        .pointer("/reasoning/effort")
+        .filter(|v| !v.is_null())
+    {
+        parameters.insert("reasoning_effort".to_string(), effort.clone());
+    }
+    if let Some(format) = request.pointer("/text/format").filter(|v| !v.is_null()) {
+        parameters.insert("response_format".to_string(), format.clone());
+    }
+    parameters
+}
+
+/// The `gen_ai.request.*` attributes of the model parameters that have one
+pub(crate) fn parameter_attributes(parameters: &Map<String, Value>) -> Vec<KeyValue> {
+    let mut attributes = Vec::new();
+    for (key, attribute) in [
+        ("temperature", GEN_AI_REQUEST_TEMPERATURE),
+        ("top_p", GEN_AI_REQUEST_TOP_P),
+        ("presence_penalty", GEN_AI_REQUEST_PRESENCE_PENALTY),
+        ("frequency_penalty", GEN_AI_REQUEST_FREQUENCY_PENALTY),
+    ] {
+        if let Some(value) = parameters.get(key).and_then(|v| v.as_f64()) {
+            attributes.push(KeyValue::new(attribute, value));
+        }
+    }
+    if let Some(max_tokens) = ["max_tokens", "max_completion_tokens", "max_output_tokens"]
+        .iter()
+        .find_map(|key| parameters.get(*key).and_then(|v| v.as_i64()))
+    {
+        attributes.push(KeyValue::new(GEN_AI_REQUEST_MAX_TOKENS, max_tokens));
+    }
+    if let Some(seed) = parameters.get("seed").and_then(|v| v.as_i64()) {
+        attributes.push(KeyValue::new(GEN_AI_OPENAI_REQUEST_SEED, seed));
+    }
+    if let Some(stop) = parameters.get("stop").and_then(stop_sequences) {
+        attributes.push(KeyValue::new(
+            GEN_AI_REQUEST_STOP_SEQUENCES,
+            opentelemetry::Value::Array(stop.into()),
+        ));
+    }
+    // Either a plain string (audio) or an object with a `type` (chat, responses)
+    if let Some(format) = parameters.get("response_format").and_then(|format| {
+        format
+            .as_str()
+            .or_else(|| format.get("type").and_then(|t| t.as_str()))
+    }) {
+        attributes.push(KeyValue::new(
+            GEN_AI_OPENAI_REQUEST_RESPONSE_FORMAT,
+            format.to_string(),
+        ));
+    }
+    if let Some(tier) = parameters.get("service_tier").and_then(|v| v.as_str()) {
+        attributes.push(KeyValue::new(
+            GEN_AI_OPENAI_REQUEST_SERVICE_TIER,
+            tier.to_string(),
+        ));
+    }
+    attributes
+}
+
+/// `stop` is either a single sequence or a list of them
+fn stop_sequences(stop: &Value) -> Option<Vec<StringValue>> {
+    match stop {
+        Value::String(sequence) => Some(vec![sequence.clone().into()]),
+        Value::Array(sequences) => Some(
+            sequences
+                .iter()
+                .filter_map(|s| s.as_str())
+                .map(|s| s.to_string().into())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn attribute(attributes: &[KeyValue], key: &str) -> Option<opentelemetry::Value> {
+        attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    }
+
+    #[test]
+    fn test_chat_parameters() {
+        let parameters = extract_model_parameters(&json!({
+            "model": "gpt-4o",
+            "messages": [],
+            "temperature": 0.2,
+            "max_completion_tokens": 256,
+            "seed": 7,
+            "stop": "END",
+            "response_format": {"type": "json_object"},
+            "tool_choice": "auto"
+        }));
+        let attributes = parameter_attributes(&parameters);
+
+        assert_eq!(
+            attribute(&attributes, GEN_AI_REQUEST_TEMPERATURE),
+            Some(0.2.into())
+        );
+        assert_eq!(
+            attribute(&attributes, GEN_AI_REQUEST_MAX_TOKENS),
+            Some(256i64.into())
+        );
+        assert_eq!(
+            attribute(&attributes, GEN_AI_OPENAI_REQUEST_SEED),
+            Some(7i64.into())
+        );
+        assert_eq!(
+            attribute(&attributes, GEN_AI_REQUEST_STOP_SEQUENCES),
+            Some(opentelemetry::Value::Array(
+                vec![StringValue::from("END")].into()
+            ))
+        );
+        assert_eq!(
+            attribute(&attributes, GEN_AI_OPENAI_REQUEST_RESPONSE_FORMAT),
+            Some("json_object".into())
+        );
+        assert_eq!(
+            Value::Object(parameters),
+            json!({
+                "temperature": 0.2,
+                "max_completion_tokens": 256,
+                "seed": 7,
+                "stop": "END",
+                "response_format": {"type": "json_object"},
+                "tool_choice": "auto"
+            })
+        );
+    }
+
+    #[test]
+    fn test_responses_parameters() {
+        let parameters = extract_model_parameters(&json!({
+            "model": "o4-mini",
+            "input": "Hi",
+            "max_output_tokens": 1000,
+            "reasoning": {"effort": "high"},
+            "text": {"format": {"type": "json_schema", "name": "answer"}}
+        }));
+        let attributes = parameter_attributes(&parameters);
+
+        assert_eq!(
+            attribute(&attributes, GEN_AI_REQUEST_MAX_TOKENS),
+            Some(1000i64.into())
+        );
+        assert_eq!(
+            attribute(&attributes, GEN_AI_OPENAI_REQUEST_RESPONSE_FORMAT),
+            Some("json_schema".into())
+        );
+        assert_eq!(parameters["reasoning_effort"], "high");
+    }
+
+    #[test]
+    fn test_no_parameters() {
+        assert!(extract_model_parameters(&json!({"model": "gpt-4o", "messages": []})).is_empty());
+    }
+}
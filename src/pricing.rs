@@ -0,0 +1,341 @@
+//! Model prices for computing the cost of a call
+//!
+//! Prices are in USD per million tokens. A [`PricingTable`] maps model names (and
+//! deployment aliases, e.g. Azure deployment names) to a [`ModelPrice`]. Dated model
+//! versions such as `gpt-4o-mini-2024-07-18` are priced as their base model; any other
+//! model that is not listed is reported rather than priced.
+
+use crate::usage::Usage;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Total cost of a call in USD
+pub(crate) const GEN_AI_USAGE_COST: &str = "gen_ai.usage.cost";
+/// Model of a call that could not be priced because it is not in the pricing table
+pub(crate) const OPENAI_PRICING_UNKNOWN_MODEL: &str = "openai.pricing.unknown_model";
+
+/// The cost of a call in USD
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Cost {
+    /// Cost per usage detail (e.g. `input`, `input_cached_tokens`) and the `total`
+    pub(crate) details: Map<String, Value>,
+    pub(crate) total: f64,
+}
+
+/// Prices bundled with this crate, see [`PricingTable::bundled`]
+const BUNDLED_PRICING: &str = include_str!("../data/pricing.json");
+
+/// Prices of one model in USD per million tokens.
+///
+/// The prices of specific kinds of tokens default to the plain input or output price.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_input: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_input: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_output: Option<f64>,
+}
+
+impl ModelPrice {
+    pub fn new(input: f64, output: f64) -> Self {
+        Self {
+            input,
+            output,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_cached_input(mut self, price: f64) -> Self {
+        self.cached_input = Some(price);
+        self
+    }
+
+    pub fn with_audio_input(mut self, price: f64) -> Self {
+        self.audio_input = Some(price);
+        self
+    }
+
+    pub fn with_image_input(mut self, price: f64) -> Self {
+        self.image_input = Some(price);
+        self
+    }
+
+    pub fn with_reasoning(mut self, price: f64) -> Self {
+        self.reasoning = Some(price);
+        self
+    }
+
+    pub fn with_audio_output(mut self, price: f64) -> Self {
+        self.audio_output = Some(price);
+        self
+    }
+
+    /// Price per million tokens of a usage detail (e.g. `input_cached_tokens`)
+    fn price_of(&self, detail: &str) -> Option<f64> {
+        let price = match detail {
+            "input" => self.input,
+            "output" => self.output,
+            "input_cached_tokens" => self.cached_input.unwrap_or(self.input),
+            "input_audio_tokens" => self.audio_input.unwrap_or(self.input),
+            "input_image_tokens" => self.image_input.unwrap_or(self.input),
+            "output_reasoning_tokens" => self.reasoning.unwrap_or(self.output),
+            "output_audio_tokens" => self.audio_output.unwrap_or(self.output),
+            // Other token details (e.g. prediction tokens) are billed as plain tokens
+            _ if detail.ends_with("_tokens") && detail.starts_with("input_") => self.input,
+            _ if detail.ends_with("_tokens") && detail.starts_with("output_") => self.output,
+            _ => return None,
+        };
+        Some(price)
+    }
+}
+
+/// Prices of models, keyed by model name and deployment alias
+///
+/// # Example
+///
+/// ```rust
+/// use reqwest_openai_tracing::{ModelPrice, PricingTable};
+///
+/// let pricing = PricingTable::bundled()
+///     .with_model("ft:gpt-4o-mini:acme", ModelPrice::new(0.3, 1.2))
+///     .with_alias("prod-chat", "gpt-4o");
+/// assert!(pricing.price("prod-chat").is_some());
+/// assert!(pricing.price("gpt-4o-2024-08-06").is_some());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PricingTable {
+    #[serde(default)]
+    models: HashMap<String, ModelPrice>,
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+}
+
+impl PricingTable {
+    /// An empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The prices of common OpenAI models bundled with this crate.
+    ///
+    /// These reflect public list prices at the time of release; load your own table to
+    /// account for price changes or negotiated rates.
+    pub fn bundled() -> Self {
+        Self::from_json_str(BUNDLED_PRICING).expect("bundled pricing table is valid")
+    }
+
+    /// Parse a table from JSON: `{"models": {"<model>": {"input": .., "output": ..}}, "aliases": {..}}`
+    pub fn from_json_str(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Parse a table from TOML with `[models.<model>]` tables and an `[aliases]` table
+    pub fn from_toml_str(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    /// Load a table from a `.json` or `.toml` file
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Ok(Self::from_toml_str(&content)?),
+            Some("json") => Ok(Self::from_json_str(&content)?),
+            _ => Err(format!("unsupported pricing file format: {}", path.display()).into()),
+        }
+    }
+
+    /// Add or replace the prices of a model
+    pub fn with_model(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.models.insert(model.into(), price);
+        self
+    }
+
+    /// Price `alias` (e.g. a deployment name) as `model`
+    pub fn with_alias(mut self, alias: impl Into<String>, model: impl Into<String>) -> Self {
+        self.aliases.insert(alias.into(), model.into());
+        self
+    }
+
+    /// Add the models and aliases of `other`, replacing those already present
+    pub fn merge(mut self, other: PricingTable) -> Self {
+        self.models.extend(other.models);
+        self.aliases.extend(other.aliases);
+        self
+    }
+
+    /// The prices of a model or alias, falling back to the model the given name is a
+    /// dated snapshot of (e.g. `gpt-4o-2024-08-06` or `gpt-4-0613`). Other variants, such
+    /// as `o1-pro` or `gpt-4o-realtime-preview`, are priced differently from their base
+    /// model and are only priced when listed.
+    pub fn price(&self, model: &str) -> Option<&ModelPrice> {
+        let model = self.aliases.get(model).map(String::as_str).unwrap_or(model);
+        if let Some(price) = self.models.get(model) {
+            return Some(price);
+        }
+        self.models
+            .iter()
+            .find(|(name, _)| {
+                model
+                    .strip_prefix(name.as_str())
+                    .is_some_and(is_snapshot_suffix)
+            })
+            .map(|(_, price)| price)
+    }
+
+    /// The cost of the usage of a call, or `None` if none of `models` (tried in order) has
+    /// a price or none of the usage is priced. `Err` holds the model that could not be
+    /// priced.
+    pub(crate) fn cost<'a>(
+        &self,
+        models: &[&'a str],
+        usage: &Usage,
+    ) -> Result<Option<Cost>, &'a str> {
+        let Some(price) = models.iter().find_map(|model| self.price(model)) else {
+            return match models.first() {
+                Some(model) => Err(model),
+                None => Ok(None),
+            };
+        };
+
+        let mut details = Map::new();
+        let mut total = 0.0;
+        for (detail, count) in &usage.details {
+            let (Some(unit_price), Some(count)) = (price.price_of(detail), count.as_f64()) else {
+                continue;
+            };
+            let cost = count * unit_price / 1_000_000.0;
+            total += cost;
+            details.insert(detail.clone(), cost.into());
+        }
+        if details.is_empty() {
+            return Ok(None);
+        }
+        details.insert("total".to_string(), total.into());
+        Ok(Some(Cost { details, total }))
+    }
+}
+
+/// Whether `suffix` is the date of a model snapshot: `-YYYY-MM-DD` or `-MMDD`
+fn is_snapshot_suffix(suffix: &str) -> bool {
+    let Some(date) = suffix.strip_prefix('-') else {
+        return false;
+    };
+    let digits =
+        |part: &str, len: usize| part.len() == len && part.bytes().all(|b| b.is_ascii_digit());
+    match date.split('-').collect::<Vec<_>>()[..] {
+        [year, month, day] => digits(year, 4) && digits(month, 2) && digits(day, 2),
+        [month_day] => digits(month_day, 4),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_lookup_by_alias_and_dated_version() {
+        let pricing = PricingTable::bundled().with_alias("prod-chat", "gpt-4o-mini");
+
+        assert_eq!(pricing.price("gpt-4o").map(|p| p.input), Some(2.5));
+        assert_eq!(
+            pricing.price("gpt-4o-mini-2024-07-18").map(|p| p.input),
+            Some(0.15)
+        );
+        assert_eq!(pricing.price("prod-chat").map(|p| p.input), Some(0.15));
+        assert_eq!(
+            pricing.price("gpt-3.5-turbo-0125").map(|p| p.input),
+            Some(0.5)
+        );
+        assert_eq!(pricing.price("gpt-4omni"), None);
+        assert_eq!(pricing.price("llama-3"), None);
+    }
+
+    #[test]
+    fn test_from_toml_and_json() {
+        let toml = PricingTable::from_toml_str(
+            r#"
+            [models.my-model]
+            input = 1.0
+            output = 2.0
+            cached_input = 0.5
+
+            [aliases]
+            my-deployment = "my-model"
+            "#,
+        )
+        .unwrap();
+        let json = PricingTable::from_json_str(
+            r#"{"models": {"my-model": {"input": 1.0, "output": 2.0, "cached_input": 0.5}},
+                "aliases": {"my-deployment": "my-model"}}"#,
+        )
+        .unwrap();
+        let code = PricingTable::new()
+            .with_model("my-model", ModelPrice::new(1.0, 2.0).with_cached_input(0.5))
+            .with_alias("my-deployment", "my-model");
+
+        assert_eq!(toml, json);
+        assert_eq!(toml, code);
+    }
+
+    #[test]
+    fn test_cost() {
+        let pricing = PricingTable::new().with_model(
+            "o4-mini",
+            ModelPrice::new(1.0, 4.0)
+                .with_cached_input(0.25)
+                .with_reasoning(8.0),
+        );
+        let usage = Usage::parse(&json!({
+            "input_tokens": 3_000_000,
+            "output_tokens": 2_000_000,
+            "total_tokens": 5_000_000,
+            "input_tokens_details": {"cached_tokens": 2_000_000},
+            "output_tokens_details": {"reasoning_tokens": 1_000_000}
+        }));
+
+        let cost = pricing
+            .cost(&["o4-mini-2025-04-16"], &usage)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Value::Object(cost.details),
+            json!({
+                "input_cached_tokens": 0.5,
+                "output_reasoning_tokens": 8.0,
+                "input": 1.0,
+                "output": 4.0,
+                "total": 13.5
+            })
+        );
+        assert_eq!(cost.total, 13.5);
+    }
+
+    #[test]
+    fn test_unknown_model_is_reported() {
+        let usage = Usage::parse(&json!({"prompt_tokens": 10, "completion_tokens": 5}));
+        assert_eq!(
+            PricingTable::new().cost(&["mystery-model"], &usage),
+            Err("mystery-model")
+        );
+
+        // Variants of a listed model are priced differently, so they are not priced as it
+        let pricing = PricingTable::bundled();
+        for model in ["o1-pro", "gpt-5-pro", "gpt-4o-realtime-preview"] {
+            assert_eq!(pricing.price(model), None, "{}", model);
+        }
+        assert_eq!(pricing.cost(&["o1-pro"], &usage), Err("o1-pro"));
+    }
+}
@@ -0,0 +1,470 @@
+//! Redaction of recorded prompts and completions
+//!
+//! Request input and response output are recorded as JSON span attributes. A [`Redactor`]
+//! rewrites (or suppresses) that JSON before it is recorded, so personal data and secrets
+//! don't reach the tracing backend. Redactors are configured on the middleware with
+//! [`with_redactor`](crate::OpenAITracingMiddlewareBuilder::with_redactor) and per
+//! request on a [`LangfuseContext`](crate::LangfuseContext); they apply in that order.
+//!
+//! Built-in redactors:
+//!
+//! - [`RegexRedactor`]: masks matches of regular expressions in every string value, with a
+//!   [preset](RegexRedactor::pii) for emails, phone numbers, credit cards and API keys
+//! - [`JsonPathRedactor`]: removes the fields at JSON paths such as `$.messages[*].name`
+//! - [`SuppressContent`]: drops the content altogether
+//!
+//! Any `Fn(ContentKind, Value) -> Option<Value>` closure is a redactor as well.
+
+use regex::Regex;
+use serde_json::Value;
+
+/// Which content is being recorded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentKind {
+    /// The request input, e.g. the messages of a chat completion
+    Input,
+    /// The response output, e.g. the choices of a chat completion
+    Output,
+}
+
+/// Rewrites content before it is recorded on a span
+pub trait Redactor: Send + Sync {
+    /// Return the content to record, or `None` to record nothing
+    fn redact(&self, kind: ContentKind, content: Value) -> Option<Value>;
+}
+
+impl<F> Redactor for F
+where
+    F: Fn(ContentKind, Value) -> Option<Value> + Send + Sync,
+{
+    fn redact(&self, kind: ContentKind, content: Value) -> Option<Value> {
+        self(kind, content)
+    }
+}
+
+/// Masks the matches of regular expressions in every string value
+///
+/// # Example
+///
+/// ```rust
+/// use reqwest_openai_tracing::{ContentKind, Redactor, RegexRedactor};
+/// use serde_json::json;
+///
+/// let redactor = RegexRedactor::pii()
+///     .with_pattern(r"ACME-\d{6}", "[ACCOUNT]")
+///     .unwrap();
+/// let redacted = redactor.redact(
+///     ContentKind::Input,
+///     json!({"messages": [{"role": "user", "content": "I'm jane@example.com, account ACME-123456"}]}),
+/// );
+/// assert_eq!(
+///     redacted.unwrap()["messages"][0]["content"],
+///     "I'm [EMAIL], account [ACCOUNT]"
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RegexRedactor {
+    rules: Vec<(Regex, String)>,
+}
+
+impl RegexRedactor {
+    /// A redactor without rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mask API keys and bearer tokens, emails, credit card numbers and phone numbers
+    pub fn pii() -> Self {
+        // API keys first, their random parts may look like numbers
+        [
+            (
+                r"\b(?:sk|pk|rk)-[A-Za-z0-9_-]{16,}|\bAKIA[0-9A-Z]{16}\b|(?i:bearer)\s+[A-Za-z0-9._~+/-]{16,}=*",
+                "[API_KEY]",
+            ),
+            (
+                r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b",
+                "[EMAIL]",
+            ),
+            (r"\b(?:\d[ -]?){12,18}\d\b", "[CREDIT_CARD]"),
+            (
+                r"(?:\+\d{1,3}[ .-]?)?\(?\b\d{3}\)?[ .-]?\d{3}[ .-]?\d{4}\b",
+                "[PHONE]",
+            ),
+        ]
+        .into_iter()
+        .fold(Self::new(), |redactor, (pattern, replacement)| {
+            redactor
+                .with_pattern(pattern, replacement)
+                .expect("built-in patterns are valid")
+        })
+    }
+
+    /// Replace the matches of `pattern` with `replacement`, which may refer to capture
+    /// groups as `$1` or `$name`
+    pub fn with_pattern(
+        self,
+        pattern: &str,
+        replacement: impl Into<String>,
+    ) -> Result<Self, regex::Error> {
+        Ok(self.with_regex(Regex::new(pattern)?, replacement))
+    }
+
+    /// Replace the matches of `regex` with `replacement`
+    pub fn with_regex(mut self, regex: Regex, replacement: impl Into<String>) -> Self {
+        self.rules.push((regex, replacement.into()));
+        self
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::String(s) => {
+                for (regex, replacement) in &self.rules {
+                    if let std::borrow::Cow::Owned(replaced) =
+                        regex.replace_all(s, replacement.as_str())
+                    {
+                        *s = replaced;
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            Value::Object(fields) => fields
+                .values_mut()
+                .for_each(|field| self.redact_value(field)),
+            _ => {}
+        }
+    }
+}
+
+impl Redactor for RegexRedactor {
+    fn redact(&self, _kind: ContentKind, mut content: Value) -> Option<Value> {
+        self.redact_value(&mut content);
+        Some(content)
+    }
+}
+
+/// One step of a JSON path
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    /// `.name` or `['name']`
+    Field(String),
+    /// `[3]`
+    Index(usize),
+    /// `.*` or `[*]`
+    Wildcard,
+    /// `..name`: the field at any depth
+    Descendant(String),
+}
+
+/// Removes the fields at JSON paths
+///
+/// Paths start at the recorded content (`$`, which may be omitted) and support fields
+/// (`.name`, `['name']`), array indices (`[0]`), wildcards (`.*`, `[*]`) and fields at
+/// any depth (`..name`).
+///
+/// # Example
+///
+/// ```rust
+/// use reqwest_openai_tracing::{ContentKind, JsonPathRedactor, Redactor};
+/// use serde_json::json;
+///
+/// let redactor = JsonPathRedactor::new(["$.messages[*].name", "$..user"]).unwrap();
+/// let redacted = redactor.redact(
+///     ContentKind::Input,
+///     json!({"messages": [{"role": "user", "name": "jane", "content": "Hi"}], "metadata": {"user": "jane"}}),
+/// );
+/// assert_eq!(
+///     redacted.unwrap(),
+///     json!({"messages": [{"role": "user", "content": "Hi"}], "metadata": {}})
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct JsonPathRedactor {
+    paths: Vec<Vec<Segment>>,
+}
+
+impl JsonPathRedactor {
+    /// Remove the fields at every one of `paths`
+    pub fn new<I, S>(paths: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let paths = paths
+            .into_iter()
+            .map(|path| parse_path(path.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { paths })
+    }
+}
+
+impl Redactor for JsonPathRedactor {
+    fn redact(&self, _kind: ContentKind, mut content: Value) -> Option<Value> {
+        for path in &self.paths {
+            remove_path(&mut content, path);
+        }
+        Some(content)
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let rest = match path.strip_prefix('$') {
+        Some(rest) => rest.to_string(),
+        // A path without `$` may start with a bare field name
+        None if !path.starts_with(['.', '[']) => format!(".{}", path),
+        None => path.to_string(),
+    };
+    match parse_segments(&rest) {
+        Some(segments) if !segments.is_empty() => Ok(segments),
+        _ => Err(format!("invalid JSON path: {}", path)),
+    }
+}
+
+fn parse_segments(mut rest: &str) -> Option<Vec<Segment>> {
+    let field_end = |s: &str| s.find(['.', '[']).unwrap_or(s.len());
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("..") {
+            let end = field_end(after);
+            if end == 0 {
+                return None;
+            }
+            segments.push(Segment::Descendant(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = field_end(after);
+            segments.push(match &after[..end] {
+                "" => return None,
+                "*" => Segment::Wildcard,
+                name => Segment::Field(name.to_string()),
+            });
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            let inner = &after[..end];
+            segments.push(if inner == "*" {
+                Segment::Wildcard
+            } else if let Some(name) = inner
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+            {
+                Segment::Field(name.to_string())
+            } else {
+                Segment::Index(inner.parse().ok()?)
+            });
+            rest = &after[end + 1..];
+        } else {
+            return None;
+        }
+    }
+    Some(segments)
+}
+
+/// Remove what `path` points at within `value`
+fn remove_path(value: &mut Value, path: &[Segment]) {
+    let Some((segment, rest)) = path.split_first() else {
+        return;
+    };
+    let last = rest.is_empty();
+    match (segment, value) {
+        (Segment::Field(name), Value::Object(fields)) => {
+            if last {
+                fields.shift_remove(name);
+            } else if let Some(field) = fields.get_mut(name) {
+                remove_path(field, rest);
+            }
+        }
+        (Segment::Index(index), Value::Array(items)) => {
+            if last {
+                if *index < items.len() {
+                    items.remove(*index);
+                }
+            } else if let Some(item) = items.get_mut(*index) {
+                remove_path(item, rest);
+            }
+        }
+        (Segment::Wildcard, Value::Object(fields)) => {
+            if last {
+                fields.clear();
+            } else {
+                fields
+                    .values_mut()
+                    .for_each(|field| remove_path(field, rest));
+            }
+        }
+        (Segment::Wildcard, Value::Array(items)) => {
+            if last {
+                items.clear();
+            } else {
+                items.iter_mut().for_each(|item| remove_path(item, rest));
+            }
+        }
+        (Segment::Descendant(name), value) => {
+            // Apply the remaining path wherever the field occurs
+            if let Value::Object(fields) = value {
+                if last {
+                    fields.shift_remove(name);
+                } else if let Some(field) = fields.get_mut(name) {
+                    remove_path(field, rest);
+                }
+            }
+            match value {
+                Value::Object(fields) => fields
+                    .values_mut()
+                    .for_each(|field| remove_path(field, path)),
+                Value::Array(items) => items.iter_mut().for_each(|item| remove_path(item, path)),
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Fields whose string values describe the structure of content rather than its text
+const METADATA_FIELDS: &[&str] = &[
+    "role",
+    "type",
+    "name",
+    "id",
+    "call_id",
+    "tool_call_id",
+    "status",
+    "finish_reason",
+    "model",
+    "voice",
+    "language",
+    "format",
+    "response_format",
+    "detail",
+    "filename",
+    "content_type",
+];
+
+/// Reduce content to its structure: every string is replaced by its length, except the
+/// values of fields such as `role`, `type` or `name` that describe the structure
+pub(crate) fn strip_text(content: Value) -> Value {
+    match content {
+        Value::String(s) => Value::String(format!("[{} chars]", s.chars().count())),
+        Value::Array(items) => Value::Array(items.into_iter().map(strip_text).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| match value {
+                    Value::String(_) if METADATA_FIELDS.contains(&key.as_str()) => (key, value),
+                    value => (key, strip_text(value)),
+                })
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Records no content at all, e.g. for the output of a client handling medical data
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SuppressContent;
+
+impl Redactor for SuppressContent {
+    fn redact(&self, _kind: ContentKind, _content: Value) -> Option<Value> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pii_patterns() {
+        let redacted = RegexRedactor::pii()
+            .redact(
+                ContentKind::Input,
+                json!({
+                    "email": "Contact jane.doe+work@example.co.uk today",
+                    "phone": "Call +1 (555) 123-4567 or 555.987.6543",
+                    "card": "Card 4111 1111 1111 1111 expires 12/27",
+                    "key": "Use sk-proj-abcdefghijklmnopqrstuvwx and Bearer eyJhbGciOiJIUzI1NiJ9.abc",
+                    "count": 12345
+                }),
+            )
+            .unwrap();
+        assert_eq!(redacted["email"], "Contact [EMAIL] today");
+        assert_eq!(redacted["phone"], "Call [PHONE] or [PHONE]");
+        assert_eq!(redacted["card"], "Card [CREDIT_CARD] expires 12/27");
+        assert_eq!(redacted["key"], "Use [API_KEY] and [API_KEY]");
+        assert_eq!(redacted["count"], 12345);
+    }
+
+    #[test]
+    fn test_json_paths() {
+        let content = json!({
+            "messages": [
+                {"role": "system", "content": "Be nice", "name": "policy"},
+                {"role": "user", "content": "Hi", "name": "jane"}
+            ],
+            "metadata": {"user": {"id": 1}, "nested": [{"user": 2, "keep": true}]}
+        });
+        let redactor =
+            JsonPathRedactor::new(["$.messages[0]", "messages[*].name", "$..user"]).unwrap();
+        assert_eq!(
+            redactor.redact(ContentKind::Input, content).unwrap(),
+            json!({
+                "messages": [{"role": "user", "content": "Hi"}],
+                "metadata": {"nested": [{"keep": true}]}
+            })
+        );
+
+        let redactor = JsonPathRedactor::new(["$['messages'][*]"]).unwrap();
+        assert_eq!(
+            redactor
+                .redact(ContentKind::Input, json!({"messages": [1, 2]}))
+                .unwrap(),
+            json!({"messages": []})
+        );
+
+        assert!(JsonPathRedactor::new(["$.messages["]).is_err());
+        assert!(JsonPathRedactor::new(["$"]).is_err());
+    }
+
+    #[test]
+    fn test_strip_text_keeps_structure() {
+        let output = json!({"choices": [{
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{"id": "call_1", "type": "function", "name": "get_weather", "arguments": {"city": "Paris"}}]
+            },
+            "finish_reason": "tool_calls"
+        }]});
+        assert_eq!(
+            strip_text(output),
+            json!({"choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{"id": "call_1", "type": "function", "name": "get_weather", "arguments": {"city": "[5 chars]"}}]
+                },
+                "finish_reason": "tool_calls"
+            }]})
+        );
+    }
+
+    #[test]
+    fn test_closure_redactor() {
+        let suppress_output = |kind: ContentKind, content: Value| match kind {
+            ContentKind::Input => Some(content),
+            ContentKind::Output => None,
+        };
+        assert!(suppress_output
+            .redact(ContentKind::Input, json!("hi"))
+            .is_some());
+        assert!(suppress_output
+            .redact(ContentKind::Output, json!("hi"))
+            .is_none());
+        assert!(SuppressContent
+            .redact(ContentKind::Input, json!("hi"))
+            .is_none());
+    }
+}
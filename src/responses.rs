@@ -0,0 +1,256 @@
+//! Support for the OpenAI Responses API (`/responses`)
+//!
+//! The Responses API uses different request and response shapes than chat completions:
+//! the input is `input` (a string or a list of items) plus optional `instructions`, the
+//! output is a list of typed items (messages, reasoning, function calls), and usage is
+//! reported as `input_tokens`/`output_tokens`.
+
+use serde_json::{json, Map, Value};
+
+/// Observation input of a Responses API request
+pub(crate) fn extract_input(request: &Value) -> Option<Value> {
+    let mut input = Map::new();
+    if let Some(instructions) = request.get("instructions").filter(|v| !v.is_null()) {
+        input.insert("instructions".to_string(), instructions.clone());
+    }
+    if let Some(items) = request.get("input") {
+        input.insert("input".to_string(), items.clone());
+    }
+    if input.is_empty() {
+        None
+    } else {
+        Some(Value::Object(input))
+    }
+}
+
+/// Observation output of a Responses API response: the output items reduced to their
+/// readable content
+pub(crate) fn extract_output(response: &Value) -> Option<Value> {
+    let items = response.get("output")?.as_array()?;
+    let output: Vec<Value> = items.iter().map(simplify_output_item).collect();
+    Some(json!({ "output": output }))
+}
+
+fn simplify_output_item(item: &Value) -> Value {
+    match item.get("type").and_then(|t| t.as_str()) {
+        Some("message") => {
+            let mut message = Map::new();
+            message.insert("type".to_string(), json!("message"));
+            message.insert(
+                "role".to_string(),
+                item.get("role").cloned().unwrap_or(json!("assistant")),
+            );
+            let parts = item.get("content").and_then(|c| c.as_array());
+            let text = join_parts(parts, "output_text", "text");
+            let refusal = join_parts(parts, "refusal", "refusal");
+            message.insert("content".to_string(), json!(text));
+            if let Some(refusal) = refusal {
+                message.insert("refusal".to_string(), json!(refusal));
+            }
+            Value::Object(message)
+        }
+        Some("reasoning") => {
+            let summary = join_parts(
+                item.get("summary").and_then(|s| s.as_array()),
+                "summary_text",
+                "text",
+            );
+            json!({ "type": "reasoning", "summary": summary })
+        }
+        Some("function_call") => json!({
+            "type": "function_call",
+            "call_id": item.get("call_id"),
+            "name": item.get("name"),
+            "arguments": item.get("arguments"),
+        }),
+        // Other items (web/file search calls, ...) are recorded as returned
+        _ => item.clone(),
+    }
+}
+
+/// Concatenate the `field` of all parts of the given type, if there are any
+fn join_parts(parts: Option<&Vec<Value>>, part_type: &str, field: &str) -> Option<String> {
+    let texts: Vec<&str> = parts?
+        .iter()
+        .filter(|part| part.get("type").and_then(|t| t.as_str()) == Some(part_type))
+        .filter_map(|part| part.get(field).and_then(|t| t.as_str()))
+        .collect();
+    if texts.is_empty() {
+        None
+    } else {
+        Some(texts.concat())
+    }
+}
+
+/// The reason a response stopped: why it is incomplete, otherwise its status
+pub(crate) fn finish_reasons(response: &Value) -> Vec<String> {
+    response
+        .pointer("/incomplete_details/reason")
+        .or_else(|| response.get("status"))
+        .and_then(|reason| reason.as_str())
+        .map(|reason| vec![reason.to_string()])
+        .unwrap_or_default()
+}
+
+/// The status message and `error.type` of a response that failed or is incomplete
+pub(crate) fn response_error(response: &Value) -> Option<(String, String)> {
+    match response.get("status")?.as_str()? {
+        "failed" => {
+            let error = response.get("error");
+            let field = |key| error.and_then(|e| e.get(key)).and_then(|v| v.as_str());
+            let message = match field("message") {
+                Some(message) => format!("Response failed: {}", message),
+                None => "Response failed".to_string(),
+            };
+            Some((message, field("code").unwrap_or("failed").to_string()))
+        }
+        "incomplete" => {
+            let reason = response
+                .pointer("/incomplete_details/reason")
+                .and_then(|v| v.as_str())
+                .unwrap_or("incomplete");
+            Some((
+                format!("Response incomplete: {}", reason),
+                reason.to_string(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Assembles the final response of a streamed Responses API call from its events
+#[derive(Default)]
+pub(crate) struct ResponseStreamAccumulator {
+    response: Option<Value>,
+    output_text: Option<String>,
+}
+
+impl ResponseStreamAccumulator {
+    pub(crate) fn push(&mut self, event: &Value) {
+        match event.get("type").and_then(|t| t.as_str()) {
+            // Lifecycle events carry the full response object; the last one wins
+            Some(
+                "response.created"
+                | "response.in_progress"
+                | "response.completed"
+                | "response.incomplete"
+                | "response.failed",
+            ) => {
+                if let Some(response) = event.get("response") {
+                    self.response = Some(response.clone());
+                }
+            }
+            Some("response.output_text.delta") => {
+                if let Some(delta) = event.get("delta").and_then(|d| d.as_str()) {
+                    self.output_text
+                        .get_or_insert_with(String::new)
+                        .push_str(delta);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The final response, or what was received so far if the stream ended early
+    pub(crate) fn finish(&self) -> Value {
+        let mut response = self.response.clone().unwrap_or_else(|| json!({}));
+        let has_output = response
+            .get("output")
+            .and_then(|o| o.as_array())
+            .is_some_and(|o| !o.is_empty());
+        if !has_output {
+            if let (Some(text), Some(object)) = (&self.output_text, response.as_object_mut()) {
+                object.insert(
+                    "output".to_string(),
+                    json!([{
+                        "type": "message",
+                        "role": "assistant",
+                        "content": [{"type": "output_text", "text": text}],
+                    }]),
+                );
+            }
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_input() {
+        let request = json!({
+            "model": "gpt-4.1",
+            "instructions": "Be brief",
+            "input": [{"role": "user", "content": "Hi"}]
+        });
+        assert_eq!(
+            extract_input(&request),
+            Some(json!({
+                "instructions": "Be brief",
+                "input": [{"role": "user", "content": "Hi"}]
+            }))
+        );
+        assert_eq!(extract_input(&json!({"model": "gpt-4.1"})), None);
+    }
+
+    #[test]
+    fn test_extract_output_items() {
+        let response = json!({
+            "output": [
+                {"type": "reasoning", "summary": [{"type": "summary_text", "text": "Thinking"}]},
+                {"type": "function_call", "call_id": "call_1", "name": "lookup", "arguments": "{}"},
+                {"type": "message", "role": "assistant", "content": [
+                    {"type": "output_text", "text": "Hello "},
+                    {"type": "output_text", "text": "there"}
+                ]}
+            ]
+        });
+        assert_eq!(
+            extract_output(&response),
+            Some(json!({"output": [
+                {"type": "reasoning", "summary": "Thinking"},
+                {"type": "function_call", "call_id": "call_1", "name": "lookup", "arguments": "{}"},
+                {"type": "message", "role": "assistant", "content": "Hello there"}
+            ]}))
+        );
+    }
+
+    #[test]
+    fn test_finish_reasons() {
+        assert_eq!(
+            finish_reasons(&json!({"status": "completed"})),
+            vec!["completed"]
+        );
+        assert_eq!(
+            finish_reasons(&json!({
+                "status": "incomplete",
+                "incomplete_details": {"reason": "max_output_tokens"}
+            })),
+            vec!["max_output_tokens"]
+        );
+        assert!(finish_reasons(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_stream_accumulator_prefers_completed_response() {
+        let mut accumulator = ResponseStreamAccumulator::default();
+        accumulator
+            .push(&json!({"type": "response.created", "response": {"id": "resp_1", "output": []}}));
+        accumulator.push(&json!({"type": "response.output_text.delta", "delta": "Hel"}));
+        accumulator.push(&json!({"type": "response.output_text.delta", "delta": "lo"}));
+
+        // Interrupted stream: fall back to the accumulated deltas
+        let partial = accumulator.finish();
+        assert_eq!(partial["id"], "resp_1");
+        assert_eq!(partial["output"][0]["content"][0]["text"], "Hello");
+
+        accumulator.push(&json!({"type": "response.completed", "response": {
+            "id": "resp_1",
+            "output": [{"type": "message", "content": [{"type": "output_text", "text": "Hello"}]}],
+            "usage": {"input_tokens": 3, "output_tokens": 1, "total_tokens": 4}
+        }}));
+        assert_eq!(accumulator.finish()["usage"]["total_tokens"], 4);
+    }
+}
@@ -0,0 +1,457 @@
+//! Retrying of OpenAI requests
+//!
+//! [`OpenAIRetryMiddleware`] retries rate-limited and failed requests the way the official
+//! OpenAI SDKs do: it waits as long as the server asks (`retry-after-ms`, `retry-after`,
+//! or the `x-ratelimit-reset-*` of an exhausted limit), falls back to exponential backoff,
+//! honours `x-should-retry`, and never retries errors that cannot succeed on a second try
+//! such as `insufficient_quota` or `context_length_exceeded`.
+//!
+//! Place it after [`OpenAITracingMiddleware`](crate::OpenAITracingMiddleware): every
+//! attempt is then recorded as an `attempt` child span of the generation, carrying an
+//! `openai.retry` event that describes the retry decision. Placed before it, the attempts
+//! are still recorded as one generation, as with
+//! [`OpenAIRetryScopeMiddleware`](crate::OpenAIRetryScopeMiddleware).
+
+use crate::attempts::{AttemptTracker, RetryScope};
+use crate::errors::error_code;
+use crate::middleware::ResponseHead;
+use http::{Extensions, HeaderMap, StatusCode};
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::{Context, KeyValue};
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next, Result};
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+/// Name of the span event recording a retry decision
+const RETRY_EVENT: &str = "openai.retry";
+
+/// Error codes that will fail the same way when retried
+const TERMINAL_ERROR_CODES: &[&str] = &[
+    "insufficient_quota",
+    "context_length_exceeded",
+    "invalid_api_key",
+    "content_filter",
+    "model_not_found",
+];
+
+/// Error codes that are worth retrying, whatever the status
+const RETRYABLE_ERROR_CODES: &[&str] = &["rate_limit_exceeded", "server_error"];
+
+/// Middleware that retries rate-limited and transiently failed OpenAI requests
+///
+/// # Example
+///
+/// ```rust
+/// use reqwest_middleware::ClientBuilder;
+/// use reqwest_openai_tracing::{OpenAIRetryMiddleware, OpenAITracingMiddleware};
+/// use std::time::Duration;
+///
+/// let client = ClientBuilder::new(reqwest::Client::new())
+///     .with(OpenAITracingMiddleware::new())
+///     .with(
+///         OpenAIRetryMiddleware::new()
+///             .with_max_retries(5)
+///             .with_max_retry_duration(Duration::from_secs(120)),
+///     )
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct OpenAIRetryMiddleware {
+    max_retries: u32,
+    max_retry_duration: Duration,
+    initial_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for OpenAIRetryMiddleware {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            max_retry_duration: Duration::from_secs(60),
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+        }
+    }
+}
+
+impl OpenAIRetryMiddleware {
+    /// Retry up to 2 times within 60 seconds, backing off from 0.5 up to 8 seconds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum number of retries after the first attempt
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Maximum total time spent on a request, including its attempts and the delays
+    /// between them; no retry is started that would wait beyond it
+    pub fn with_max_retry_duration(mut self, duration: Duration) -> Self {
+        self.max_retry_duration = duration;
+        self
+    }
+
+    /// Delay before the first retry when the server does not say how long to wait;
+    /// doubled for every further retry, up to the maximum delay
+    pub fn with_initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Maximum backoff delay when the server does not say how long to wait
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Exponential backoff with jitter for the given retry (0-based)
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        // Spread out clients that failed at the same time: wait 75-100% of the delay
+        let random = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        let jitter = 0.75 + 0.25 * (random % 1000) as f64 / 1000.0;
+        delay.mul_f64(jitter)
+    }
+
+    /// Decide whether to retry after an attempt
+    fn decide(&self, result: &AttemptResult, retry: u32, started: Instant) -> Decision {
+        let (retryable, reason, server_delay) = match result {
+            AttemptResult::Error(reason, retryable) => (*retryable, reason.clone(), None),
+            AttemptResult::Status(status, headers, code) => {
+                let reason = code.clone().unwrap_or_else(|| status.as_u16().to_string());
+                (
+                    is_retryable(*status, headers, code.as_deref()),
+                    reason,
+                    server_delay(headers),
+                )
+            }
+        };
+
+        if !retryable {
+            return Decision::GiveUp(reason, "not retryable");
+        }
+        if retry >= self.max_retries {
+            return Decision::GiveUp(reason, "max retries reached");
+        }
+        let delay = server_delay.unwrap_or_else(|| self.backoff(retry));
+        if started.elapsed().saturating_add(delay) > self.max_retry_duration {
+            return Decision::GiveUp(reason, "max retry duration reached");
+        }
+        Decision::Retry(reason, delay)
+    }
+}
+
+/// Outcome of an attempt, as far as retrying is concerned
+enum AttemptResult {
+    /// A request error and whether it is transient
+    Error(String, bool),
+    /// An unsuccessful response: its status, headers and error code
+    Status(StatusCode, HeaderMap, Option<String>),
+}
+
+enum Decision {
+    Retry(String, Duration),
+    GiveUp(String, &'static str),
+}
+
+impl Decision {
+    fn event_attributes(&self, attempt: u32) -> Vec<KeyValue> {
+        let mut attributes = vec![KeyValue::new("openai.retry.attempt", attempt as i64)];
+        match self {
+            Decision::Retry(reason, delay) => {
+                attributes.push(KeyValue::new("openai.retry.decision", "retry"));
+                attributes.push(KeyValue::new("openai.retry.reason", reason.clone()));
+                attributes.push(KeyValue::new(
+                    "openai.retry.delay_ms",
+                    delay.as_millis() as i64,
+                ));
+            }
+            Decision::GiveUp(reason, why) => {
+                attributes.push(KeyValue::new("openai.retry.decision", "give_up"));
+                attributes.push(KeyValue::new("openai.retry.reason", reason.clone()));
+                attributes.push(KeyValue::new("openai.retry.give_up_reason", *why));
+            }
+        }
+        attributes
+    }
+}
+
+fn is_retryable(status: StatusCode, headers: &HeaderMap, code: Option<&str>) -> bool {
+    // The server knows best
+    match headers.get("x-should-retry").and_then(|v| v.to_str().ok()) {
+        Some("true") => return true,
+        Some("false") => return false,
+        _ => {}
+    }
+    if code.is_some_and(|code| TERMINAL_ERROR_CODES.contains(&code)) {
+        return false;
+    }
+    if code.is_some_and(|code| RETRYABLE_ERROR_CODES.contains(&code)) {
+        return true;
+    }
+    matches!(status.as_u16(), 408 | 409 | 429) || status.is_server_error()
+}
+
+/// How long the server asks to wait before retrying, if it says so
+fn server_delay(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    if let Some(delay) = header("retry-after-ms")
+        .and_then(|v| v.parse::<f64>().ok())
+        .and_then(|ms| seconds(ms / 1000.0))
+    {
+        return Some(delay);
+    }
+    if let Some(delay) = header("retry-after")
+        .and_then(|v| v.parse::<f64>().ok())
+        .and_then(seconds)
+    {
+        return Some(delay);
+    }
+    // Wait for the reset of whichever limit is exhausted
+    ["requests", "tokens"]
+        .into_iter()
+        .filter(|limit| header(&format!("x-ratelimit-remaining-{}", limit)) == Some("0"))
+        .filter_map(|limit| header(&format!("x-ratelimit-reset-{}", limit)))
+        .filter_map(parse_reset)
+        .max()
+}
+
+/// A delay in seconds as sent by the server: `None` unless finite, saturating when too
+/// long to represent (so the retry gives up on it rather than waiting)
+fn seconds(seconds: f64) -> Option<Duration> {
+    if !seconds.is_finite() {
+        return None;
+    }
+    Some(Duration::try_from_secs_f64(seconds.max(0.0)).unwrap_or(Duration::MAX))
+}
+
+/// Parse a rate limit reset such as `1s`, `6m0s` or `20ms`
+fn parse_reset(value: &str) -> Option<Duration> {
+    let mut total = Duration::ZERO;
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let seconds = match &rest[..unit_end] {
+            "ms" => number / 1000.0,
+            "s" => number,
+            "m" => number * 60.0,
+            "h" => number * 3600.0,
+            _ => return None,
+        };
+        total = total.saturating_add(self::seconds(seconds)?);
+        rest = &rest[unit_end..];
+    }
+    Some(total)
+}
+
+#[async_trait::async_trait]
+impl Middleware for OpenAIRetryMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let tracker = extensions.get::<AttemptTracker>().cloned();
+        // Tracing middleware placed after this one records the attempts as one generation
+        let scope_opened = RetryScope::open(extensions);
+        let started = Instant::now();
+        let mut retry = 0;
+        let mut req = req;
+
+        loop {
+            // Requests with a streaming body cannot be sent twice
+            let retry_request = req.try_clone();
+            let mut attempt = tracker.as_ref().map(|tracker| tracker.start_attempt());
+
+            let result = next.clone().run(req, extensions).await;
+            let (result, outcome) = match result {
+                Ok(res) if res.status().is_success() => (Ok(res), None),
+                Ok(mut res) => {
+                    let status = res.status();
+                    let headers = res.headers().clone();
+                    // Only read the body when its error code matters for the decision
+                    if is_retryable(status, &headers, None) {
+                        let head = ResponseHead::take(&mut res);
+                        match res.bytes().await {
+                            Ok(body) => {
+                                let code = error_code(&body);
+                                (
+                                    Ok(head.into_response(body.into())),
+                                    Some(AttemptResult::Status(status, headers, code)),
+                                )
+                            }
+                            Err(e) => (Err(reqwest_middleware::Error::Reqwest(e)), None),
+                        }
+                    } else {
+                        (Ok(res), Some(AttemptResult::Status(status, headers, None)))
+                    }
+                }
+                Err(e) => {
+                    let transient = match &e {
+                        reqwest_middleware::Error::Reqwest(e) => e.is_timeout() || e.is_connect(),
+                        reqwest_middleware::Error::Middleware(_) => false,
+                    };
+                    let outcome = AttemptResult::Error(e.to_string(), transient);
+                    (Err(e), Some(outcome))
+                }
+            };
+
+            let decision = outcome.map(|outcome| match self.decide(&outcome, retry, started) {
+                Decision::Retry(reason, _) if retry_request.is_none() => {
+                    Decision::GiveUp(reason, "request body not cloneable")
+                }
+                decision => decision,
+            });
+            if let Some(decision) = &decision {
+                let attributes = decision.event_attributes(retry + 1);
+                match attempt.as_mut() {
+                    Some(attempt) => attempt.add_event(RETRY_EVENT, attributes),
+                    None => Context::current().span().add_event(RETRY_EVENT, attributes),
+                }
+            }
+            if let Some(attempt) = attempt {
+                attempt.finish(&result);
+            }
+
+            match (decision, retry_request) {
+                (Some(Decision::Retry(_, delay)), Some(retry_request)) => {
+                    tokio::time::sleep(delay).await;
+                    req = retry_request;
+                    retry += 1;
+                }
+                _ => {
+                    if scope_opened {
+                        RetryScope::close(extensions);
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reset() {
+        assert_eq!(parse_reset("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_reset("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_reset("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(
+            parse_reset("1h2m3.5s"),
+            Some(Duration::from_secs_f64(3723.5))
+        );
+        assert_eq!(parse_reset("soon"), None);
+        assert_eq!(parse_reset(""), None);
+        // Too long to represent, e.g. from a misbehaving server
+        assert_eq!(parse_reset(&format!("{}h", "9".repeat(400))), None);
+        assert_eq!(
+            parse_reset(&format!("{}h1s", "9".repeat(30))),
+            Some(Duration::MAX)
+        );
+    }
+
+    #[test]
+    fn test_server_delay() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining-requests", "10".parse().unwrap());
+        headers.insert("x-ratelimit-reset-requests", "1s".parse().unwrap());
+        headers.insert("x-ratelimit-remaining-tokens", "0".parse().unwrap());
+        headers.insert("x-ratelimit-reset-tokens", "2.5s".parse().unwrap());
+        assert_eq!(server_delay(&headers), Some(Duration::from_millis(2500)));
+
+        headers.insert("retry-after", "7".parse().unwrap());
+        assert_eq!(server_delay(&headers), Some(Duration::from_secs(7)));
+        headers.insert("retry-after-ms", "150".parse().unwrap());
+        assert_eq!(server_delay(&headers), Some(Duration::from_millis(150)));
+
+        // Delays that are not finite are ignored, those too long to represent saturate
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after-ms", "NaN".parse().unwrap());
+        headers.insert("retry-after", "inf".parse().unwrap());
+        assert_eq!(server_delay(&headers), None);
+        headers.insert("retry-after-ms", "1e30".parse().unwrap());
+        assert_eq!(server_delay(&headers), Some(Duration::MAX));
+
+        // ...and make the retry give up rather than wait
+        let result = AttemptResult::Status(StatusCode::TOO_MANY_REQUESTS, headers, None);
+        assert!(matches!(
+            OpenAIRetryMiddleware::new().decide(&result, 0, Instant::now()),
+            Decision::GiveUp(_, "max retry duration reached")
+        ));
+    }
+
+    #[test]
+    fn test_terminal_and_retryable_errors() {
+        let headers = HeaderMap::new();
+        let too_many = StatusCode::TOO_MANY_REQUESTS;
+        assert!(is_retryable(
+            too_many,
+            &headers,
+            Some("rate_limit_exceeded")
+        ));
+        assert!(!is_retryable(
+            too_many,
+            &headers,
+            Some("insufficient_quota")
+        ));
+        assert!(!is_retryable(
+            StatusCode::BAD_REQUEST,
+            &headers,
+            Some("context_length_exceeded")
+        ));
+        assert!(is_retryable(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &headers,
+            None
+        ));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED, &headers, None));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-should-retry", "false".parse().unwrap());
+        assert!(!is_retryable(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &headers,
+            None
+        ));
+    }
+
+    #[test]
+    fn test_retry_budget() {
+        let middleware =
+            OpenAIRetryMiddleware::new().with_max_retry_duration(Duration::from_secs(5));
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", "30".parse().unwrap());
+        let rate_limited = AttemptResult::Status(
+            StatusCode::TOO_MANY_REQUESTS,
+            headers,
+            Some("rate_limit_exceeded".to_string()),
+        );
+        assert!(matches!(
+            middleware.decide(&rate_limited, 0, Instant::now()),
+            Decision::GiveUp(_, "max retry duration reached")
+        ));
+    }
+}
@@ -0,0 +1,695 @@
+//! Pass-through tracing for streaming (server-sent events) responses
+//!
+//! Streaming requests must not be buffered by the middleware, otherwise the caller
+//! loses incremental delivery. Instead the response body is wrapped in a [`TracedStream`]
+//! which forwards every chunk untouched while decoding the SSE events on the side and
+//! assembling the final output and usage. The stream holds on to the generation, so its
+//! span is ended once the stream finishes or is dropped.
+//!
+//! The arrival of the first output (content, tool call or text delta) is recorded as the
+//! completion start time and time to first token (in the conventions that have them) and
+//! a `first_token` event; the gaps between later output chunks as inter-token latency
+//! statistics.
+//!
+//! A stream dropped before its terminal event (`[DONE]`, or the final event of a Responses
+//! API stream) was cancelled by the caller: the span records the partial output with an
+//! error of type `cancelled`. A Responses API stream
+//! ending with `response.failed` records the error of the response, one ending with
+//! `response.incomplete` an error typed by the reason it is incomplete.
+
+use crate::conventions::{
+    ErrorRecord, InterTokenLatency, ResponseRecord, OPENAI_STREAM_TIME_TO_FIRST_TOKEN_MS,
+};
+use crate::generation::Generation;
+use crate::metrics::reqwest_error_type;
+use crate::responses::ResponseStreamAccumulator;
+use bytes::Bytes;
+use futures::Stream;
+use opentelemetry::trace::Status;
+use opentelemetry::KeyValue;
+use opentelemetry_semantic_conventions::attribute::ERROR_TYPE;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+type ByteStream = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>;
+
+/// Name of the span event recorded when the first output arrives
+const FIRST_TOKEN_EVENT: &str = "first_token";
+/// `error.type` of streams dropped before they completed
+const CANCELLED_ERROR_TYPE: &str = "cancelled";
+/// Events ending a Responses API stream
+const RESPONSE_TERMINAL_EVENTS: &[&str] = &[
+    "response.completed",
+    "response.incomplete",
+    "response.failed",
+];
+
+/// Incremental decoder for `text/event-stream` bodies.
+///
+/// Bytes are fed in as they arrive; complete event payloads (the joined `data:` lines)
+/// are returned once the terminating blank line has been seen.
+#[derive(Default)]
+pub(crate) struct SseDecoder {
+    buffer: Vec<u8>,
+    data: Option<String>,
+}
+
+impl SseDecoder {
+    /// Feed a chunk of the body and return the data of every event it completed
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if let Some(data) = self.data.take() {
+                    events.push(data);
+                }
+            } else if let Some(value) = line.strip_prefix("data:") {
+                let value = value.strip_prefix(' ').unwrap_or(value);
+                match self.data.as_mut() {
+                    Some(data) => {
+                        data.push('\n');
+                        data.push_str(value);
+                    }
+                    None => self.data = Some(value.to_string()),
+                }
+            }
+            // Other fields (event, id, retry) and comments don't carry payload data
+        }
+        events
+    }
+
+    /// Return the pending event, if the stream ended without a trailing blank line
+    pub(crate) fn finish(&mut self) -> Option<String> {
+        if !self.buffer.is_empty() {
+            let rest = std::mem::take(&mut self.buffer);
+            self.feed(&rest);
+            self.feed(b"\n");
+        }
+        self.data.take()
+    }
+}
+
+/// Accumulated state of a single tool call across deltas
+#[derive(Default)]
+struct ToolCallState {
+    id: Option<String>,
+    kind: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+/// Accumulated state of a single choice across deltas
+#[derive(Default)]
+struct ChoiceState {
+    role: Option<String>,
+    content: Option<String>,
+    refusal: Option<String>,
+    text: Option<String>,
+    tool_calls: BTreeMap<u64, ToolCallState>,
+    logprobs: Vec<Value>,
+    finish_reason: Option<Value>,
+}
+
+/// Rebuilds a non-streaming response body from a sequence of stream chunks.
+///
+/// The assembled JSON has the same shape as the regular (buffered) response, so it can
+/// be recorded with the same code path once the stream is complete.
+pub(crate) struct StreamAccumulator {
+    operation_type: String,
+    id: Option<Value>,
+    model: Option<Value>,
+    created: Option<Value>,
+    system_fingerprint: Option<Value>,
+    service_tier: Option<Value>,
+    choices: BTreeMap<u64, ChoiceState>,
+    usage: Option<Value>,
+    /// Responses API streams are made of typed events rather than chunks
+    responses: ResponseStreamAccumulator,
+}
+
+impl StreamAccumulator {
+    pub(crate) fn new(operation_type: &str) -> Self {
+        Self {
+            operation_type: operation_type.to_string(),
+            id: None,
+            model: None,
+            created: None,
+            system_fingerprint: None,
+            service_tier: None,
+            choices: BTreeMap::new(),
+            usage: None,
+            responses: ResponseStreamAccumulator::default(),
+        }
+    }
+
+    /// Merge one decoded chunk into the accumulated response
+    pub(crate) fn push(&mut self, chunk: &Value) {
+        if self.operation_type == "response" {
+            self.responses.push(chunk);
+            return;
+        }
+
+        for (slot, key) in [
+            (&mut self.id, "id"),
+            (&mut self.model, "model"),
+            (&mut self.created, "created"),
+            (&mut self.system_fingerprint, "system_fingerprint"),
+            (&mut self.service_tier, "service_tier"),
+        ] {
+            if let Some(value) = chunk.get(key).filter(|v| !v.is_null()) {
+                *slot = Some(value.clone());
+            }
+        }
+
+        // With `stream_options.include_usage` the final chunk carries the usage
+        if let Some(usage) = chunk.get("usage").filter(|v| !v.is_null()) {
+            self.usage = Some(usage.clone());
+        }
+
+        let Some(choices) = chunk.get("choices").and_then(|c| c.as_array()) else {
+            return;
+        };
+        for choice in choices {
+            let index = choice.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+            let state = self.choices.entry(index).or_default();
+
+            if let Some(reason) = choice.get("finish_reason").filter(|v| !v.is_null()) {
+                state.finish_reason = Some(reason.clone());
+            }
+
+            // Log probabilities arrive per delta token; keep them all
+            if let Some(logprobs) = choice
+                .pointer("/logprobs/content")
+                .and_then(|c| c.as_array())
+            {
+                state.logprobs.extend(logprobs.iter().cloned());
+            }
+
+            // Text completions stream `text` directly on the choice
+            if let Some(text) = choice.get("text").and_then(|t| t.as_str()) {
+                state.text.get_or_insert_with(String::new).push_str(text);
+            }
+
+            let Some(delta) = choice.get("delta") else {
+                continue;
+            };
+            if let Some(role) = delta.get("role").and_then(|r| r.as_str()) {
+                state.role = Some(role.to_string());
+            }
+            if let Some(content) = delta.get("content").and_then(|c| c.as_str()) {
+                state
+                    .content
+                    .get_or_insert_with(String::new)
+                    .push_str(content);
+            }
+            if let Some(refusal) = delta.get("refusal").and_then(|r| r.as_str()) {
+                state
+                    .refusal
+                    .get_or_insert_with(String::new)
+                    .push_str(refusal);
+            }
+            if let Some(tool_calls) = delta.get("tool_calls").and_then(|t| t.as_array()) {
+                for tool_call in tool_calls {
+                    let tool_index = tool_call.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                    let tool_state = state.tool_calls.entry(tool_index).or_default();
+                    if let Some(id) = tool_call.get("id").and_then(|v| v.as_str()) {
+                        tool_state.id = Some(id.to_string());
+                    }
+                    if let Some(kind) = tool_call.get("type").and_then(|v| v.as_str()) {
+                        tool_state.kind = Some(kind.to_string());
+                    }
+                    if let Some(function) = tool_call.get("function") {
+                        if let Some(name) = function.get("name").and_then(|v| v.as_str()) {
+                            tool_state.name.push_str(name);
+                        }
+                        if let Some(arguments) = function.get("arguments").and_then(|v| v.as_str())
+                        {
+                            tool_state.arguments.push_str(arguments);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Assemble the accumulated chunks into a response JSON
+    pub(crate) fn finish(&self) -> Value {
+        if self.operation_type == "response" {
+            return self.responses.finish();
+        }
+
+        let choices: Vec<Value> = self
+            .choices
+            .iter()
+            .map(|(index, state)| {
+                let mut choice = Map::new();
+                choice.insert("index".to_string(), json!(index));
+                if self.operation_type == "completion" {
+                    choice.insert("text".to_string(), json!(state.text));
+                } else {
+                    let mut message = Map::new();
+                    message.insert(
+                        "role".to_string(),
+                        json!(state.role.as_deref().unwrap_or("assistant")),
+                    );
+                    message.insert("content".to_string(), json!(state.content));
+                    if let Some(refusal) = &state.refusal {
+                        message.insert("refusal".to_string(), json!(refusal));
+                    }
+                    if !state.tool_calls.is_empty() {
+                        let tool_calls: Vec<Value> = state
+                            .tool_calls
+                            .values()
+                            .map(|tool_call| {
+                                json!({
+                                    "id": tool_call.id,
+                                    "type": tool_call.kind.as_deref().unwrap_or("function"),
+                                    "function": {
+                                        "name": tool_call.name,
+                                        "arguments": tool_call.arguments,
+                                    }
+                                })
+                            })
+                            .collect();
+                        message.insert("tool_calls".to_string(), Value::Array(tool_calls));
+                    }
+                    choice.insert("message".to_string(), Value::Object(message));
+                }
+                choice.insert(
+                    "finish_reason".to_string(),
+                    state.finish_reason.clone().unwrap_or(Value::Null),
+                );
+                if !state.logprobs.is_empty() {
+                    choice.insert("logprobs".to_string(), json!({"content": state.logprobs}));
+                }
+                Value::Object(choice)
+            })
+            .collect();
+
+        let mut response = Map::new();
+        for (key, value) in [
+            ("id", &self.id),
+            ("model", &self.model),
+            ("created", &self.created),
+            ("system_fingerprint", &self.system_fingerprint),
+            ("service_tier", &self.service_tier),
+            ("usage", &self.usage),
+        ] {
+            if let Some(value) = value {
+                response.insert(key.to_string(), value.clone());
+            }
+        }
+        response.insert("choices".to_string(), Value::Array(choices));
+        Value::Object(response)
+    }
+}
+
+/// Arrival times of the output chunks of a stream
+#[derive(Default)]
+struct TokenTiming {
+    chunks: u64,
+    last: Option<Instant>,
+    gap_sum: Duration,
+    gap_min: Option<Duration>,
+    gap_max: Option<Duration>,
+}
+
+impl TokenTiming {
+    /// Record the arrival of an output chunk; returns whether it is the first one
+    fn record(&mut self, now: Instant) -> bool {
+        self.chunks += 1;
+        let first = match self.last {
+            Some(last) => {
+                let gap = now.saturating_duration_since(last);
+                self.gap_sum += gap;
+                self.gap_min = Some(self.gap_min.map_or(gap, |min| min.min(gap)));
+                self.gap_max = Some(self.gap_max.map_or(gap, |max| max.max(gap)));
+                false
+            }
+            None => true,
+        };
+        self.last = Some(now);
+        first
+    }
+
+    /// The number of output chunks, if any
+    fn output_chunks(&self) -> Option<u64> {
+        (self.chunks > 0).then_some(self.chunks)
+    }
+
+    /// The statistics of the gaps between output chunks, once there are two
+    fn inter_token_latency(&self) -> Option<InterTokenLatency> {
+        let (min, max) = (self.gap_min?, self.gap_max?);
+        Some(InterTokenLatency {
+            mean: self.gap_sum / (self.chunks - 1) as u32,
+            min,
+            max,
+        })
+    }
+}
+
+/// How a stream ended
+enum StreamEnd<'a> {
+    /// The whole response was received
+    Completed,
+    /// Reading the body failed
+    Failed(&'a reqwest::Error),
+    /// The caller dropped the stream before the response was complete
+    Cancelled,
+}
+
+/// Everything needed to complete the span once the stream is done
+struct StreamState {
+    generation: Generation,
+    timing: TokenTiming,
+    decoder: SseDecoder,
+    accumulator: StreamAccumulator,
+    /// Whether the terminal event of the stream was received
+    completed: bool,
+}
+
+impl StreamState {
+    fn consume(&mut self, chunk: &[u8]) {
+        for data in self.decoder.feed(chunk) {
+            self.consume_event(&data);
+        }
+    }
+
+    fn consume_event(&mut self, data: &str) {
+        if data == "[DONE]" {
+            self.completed = true;
+            return;
+        }
+        if let Ok(json) = serde_json::from_str::<Value>(data) {
+            if json
+                .get("type")
+                .and_then(|t| t.as_str())
+                .is_some_and(|t| RESPONSE_TERMINAL_EVENTS.contains(&t))
+            {
+                self.completed = true;
+            }
+            if has_output(&self.accumulator.operation_type, &json) {
+                self.record_output_chunk();
+            }
+            self.accumulator.push(&json);
+        }
+    }
+
+    fn record_output_chunk(&mut self) {
+        if !self.timing.record(Instant::now()) {
+            return;
+        }
+        let mut metrics = self.generation.metrics();
+        let time_to_first_token = metrics.start_time().elapsed();
+        let span = self.generation.span();
+        let response = ResponseRecord {
+            completion_start_time: Some(SystemTime::now()),
+            time_to_first_token: Some(time_to_first_token),
+            ..ResponseRecord::new(self.generation.operation_type())
+        };
+        for attribute in self.generation.config().response_attributes(&response) {
+            span.set_attribute(attribute);
+        }
+        span.add_event(
+            FIRST_TOKEN_EVENT,
+            vec![KeyValue::new(
+                OPENAI_STREAM_TIME_TO_FIRST_TOKEN_MS,
+                time_to_first_token.as_secs_f64() * 1000.0,
+            )],
+        );
+        metrics.record_first_token();
+    }
+
+    fn finish(mut self, end: StreamEnd<'_>) {
+        if let Some(data) = self.decoder.finish() {
+            self.consume_event(&data);
+        }
+
+        let response_json = self.accumulator.finish();
+        let span = self.generation.span();
+        let mut metrics = self.generation.metrics();
+        crate::middleware::record_response_json(
+            &span,
+            &mut metrics,
+            self.generation.config(),
+            &self.accumulator.operation_type,
+            self.generation.request_model(),
+            &response_json,
+        );
+
+        let error = match end {
+            // A Responses API stream also completes with a failed or incomplete response
+            StreamEnd::Completed => match self.accumulator.operation_type.as_str() {
+                "response" => crate::responses::response_error(&response_json)
+                    .map(|(message, error_type)| (message, error_type, false)),
+                _ => None,
+            },
+            StreamEnd::Failed(e) => Some((
+                format!("Failed to read response stream: {}", e),
+                reqwest_error_type(e).to_string(),
+                false,
+            )),
+            StreamEnd::Cancelled => Some((
+                "Response stream dropped before completion".to_string(),
+                CANCELLED_ERROR_TYPE.to_string(),
+                true,
+            )),
+        };
+        match error {
+            None => span.set_status(Status::Ok),
+            Some((message, error_type, cancelled)) => {
+                span.set_attribute(KeyValue::new(ERROR_TYPE, error_type.clone()));
+                let error = ErrorRecord {
+                    cancelled,
+                    ..ErrorRecord::new(self.generation.operation_type(), &message, &error_type)
+                };
+                for attribute in self.generation.config().error_attributes(&error) {
+                    span.set_attribute(attribute);
+                }
+                span.set_status(Status::error(message));
+                metrics.set_error_type(error_type);
+            }
+        }
+
+        let response = ResponseRecord {
+            output_chunks: self.timing.output_chunks(),
+            inter_token_latency: self.timing.inter_token_latency(),
+            ..ResponseRecord::new(self.generation.operation_type())
+        };
+        for attribute in self.generation.config().response_attributes(&response) {
+            span.set_attribute(attribute);
+        }
+        // The generation ends once this was its last holder, normally the case as the
+        // request extensions are dropped when the response is returned
+    }
+}
+
+/// Whether a stream event carries generated output, as opposed to only metadata such as
+/// the role, usage or lifecycle events
+fn has_output(operation_type: &str, event: &Value) -> bool {
+    if operation_type == "response" {
+        return event
+            .get("type")
+            .and_then(|t| t.as_str())
+            .is_some_and(|t| t.ends_with(".delta"));
+    }
+    let Some(choices) = event.get("choices").and_then(|c| c.as_array()) else {
+        return false;
+    };
+    choices.iter().any(|choice| {
+        let delta = choice.get("delta").unwrap_or(choice);
+        ["content", "refusal", "tool_calls", "text"]
+            .iter()
+            .any(|key| match delta.get(*key) {
+                Some(Value::String(s)) => !s.is_empty(),
+                Some(value) => !value.is_null(),
+                None => false,
+            })
+    })
+}
+
+/// Response body stream that records the streamed output on the span while passing
+/// every chunk through unchanged.
+pub(crate) struct TracedStream {
+    inner: ByteStream,
+    state: Option<StreamState>,
+}
+
+impl TracedStream {
+    pub(crate) fn new(inner: ByteStream, generation: Generation) -> Self {
+        let operation_type = generation.operation_type();
+        Self {
+            inner,
+            state: Some(StreamState {
+                generation,
+                timing: TokenTiming::default(),
+                decoder: SseDecoder::default(),
+                accumulator: StreamAccumulator::new(operation_type),
+                completed: false,
+            }),
+        }
+    }
+}
+
+impl Stream for TracedStream {
+    type Item = reqwest::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.as_mut().poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(state) = self.state.as_mut() {
+                    state.consume(chunk);
+                }
+            }
+            Poll::Ready(Some(Err(e))) => {
+                if let Some(state) = self.state.take() {
+                    state.finish(StreamEnd::Failed(e));
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(state) = self.state.take() {
+                    state.finish(StreamEnd::Completed);
+                }
+            }
+            Poll::Pending => {}
+        }
+        poll
+    }
+}
+
+impl Drop for TracedStream {
+    fn drop(&mut self) {
+        // Clients commonly stop reading at the terminal event, without polling the end of
+        // the body; any earlier drop cancels the stream. Either way, record what was
+        // received so far
+        if let Some(state) = self.state.take() {
+            let end = match state.completed {
+                true => StreamEnd::Completed,
+                false => StreamEnd::Cancelled,
+            };
+            state.finish(end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_decoder_split_chunks() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.feed(b"data: {\"a\":").is_empty());
+        assert_eq!(
+            decoder.feed(b"1}\n\ndata: [DONE]\n\n"),
+            vec!["{\"a\":1}", "[DONE]"]
+        );
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn test_sse_decoder_crlf_and_trailing_event() {
+        let mut decoder = SseDecoder::default();
+        assert_eq!(
+            decoder.feed(b": keep-alive\r\n\r\ndata: one\r\n\r\n"),
+            vec!["one"]
+        );
+        assert!(decoder.feed(b"data: two").is_empty());
+        assert_eq!(decoder.finish(), Some("two".to_string()));
+    }
+
+    #[test]
+    fn test_accumulate_chat_stream() {
+        let mut accumulator = StreamAccumulator::new("chat");
+        let chunks = [
+            json!({"id": "chatcmpl-1", "model": "gpt-4o", "choices": [{"index": 0, "delta": {"role": "assistant", "content": ""}, "finish_reason": null}]}),
+            json!({"id": "chatcmpl-1", "model": "gpt-4o", "choices": [{"index": 0, "delta": {"content": "Hel"}, "logprobs": {"content": [{"token": "Hel", "logprob": -0.1}]}, "finish_reason": null}]}),
+            json!({"id": "chatcmpl-1", "model": "gpt-4o", "choices": [{"index": 0, "delta": {"content": "lo"}, "logprobs": {"content": [{"token": "lo", "logprob": -0.2}]}, "finish_reason": null}]}),
+            json!({"id": "chatcmpl-1", "model": "gpt-4o", "choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}),
+            json!({"id": "chatcmpl-1", "model": "gpt-4o", "choices": [], "usage": {"prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7}}),
+        ];
+        for chunk in &chunks {
+            accumulator.push(chunk);
+        }
+
+        let response = accumulator.finish();
+        assert_eq!(response["id"], "chatcmpl-1");
+        assert_eq!(response["choices"][0]["message"]["content"], "Hello");
+        assert_eq!(response["choices"][0]["finish_reason"], "stop");
+        assert_eq!(
+            response["choices"][0]["logprobs"]["content"][1]["token"],
+            "lo"
+        );
+        assert_eq!(response["usage"]["total_tokens"], 7);
+    }
+
+    #[test]
+    fn test_accumulate_tool_call_deltas() {
+        let mut accumulator = StreamAccumulator::new("chat");
+        accumulator.push(&json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "id": "call_1", "type": "function", "function": {"name": "get_weather", "arguments": ""}}]}}]}));
+        accumulator.push(&json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": "{\"city\":"}}]}}]}));
+        accumulator.push(&json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": "\"Paris\"}"}}]}, "finish_reason": "tool_calls"}]}));
+
+        let response = accumulator.finish();
+        let tool_call = &response["choices"][0]["message"]["tool_calls"][0];
+        assert_eq!(tool_call["id"], "call_1");
+        assert_eq!(tool_call["function"]["name"], "get_weather");
+        assert_eq!(tool_call["function"]["arguments"], "{\"city\":\"Paris\"}");
+        assert_eq!(response["choices"][0]["finish_reason"], "tool_calls");
+    }
+
+    #[test]
+    fn test_output_detection() {
+        assert!(!has_output(
+            "chat",
+            &json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": ""}}]})
+        ));
+        assert!(has_output(
+            "chat",
+            &json!({"choices": [{"index": 0, "delta": {"content": "Hi"}}]})
+        ));
+        assert!(!has_output(
+            "chat",
+            &json!({"choices": [], "usage": {"total_tokens": 3}})
+        ));
+        assert!(has_output(
+            "response",
+            &json!({"type": "response.output_text.delta", "delta": "Hi"})
+        ));
+        assert!(!has_output(
+            "response",
+            &json!({"type": "response.created"})
+        ));
+    }
+
+    #[test]
+    fn test_inter_token_latency() {
+        let mut timing = TokenTiming::default();
+        assert_eq!(timing.output_chunks(), None);
+
+        let start = Instant::now();
+        assert!(timing.record(start));
+        assert!(!timing.record(start + Duration::from_millis(10)));
+        assert!(!timing.record(start + Duration::from_millis(40)));
+
+        assert_eq!(timing.output_chunks(), Some(3));
+        assert_eq!(
+            timing.inter_token_latency(),
+            Some(InterTokenLatency {
+                mean: Duration::from_millis(20),
+                min: Duration::from_millis(10),
+                max: Duration::from_millis(30),
+            })
+        );
+    }
+}
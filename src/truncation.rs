@@ -0,0 +1,255 @@
+//! Size limits for recorded content
+//!
+//! Exporters reject (and OTLP batches choke on) attributes of several megabytes, as long
+//! conversations or inline images produce. Content over its limit is truncated while
+//! keeping the JSON valid: the longest strings (message texts, base64 data) are shortened
+//! first, so the structure of the content survives. Only when the structure alone exceeds
+//! the limit is the serialized JSON cut and recorded as a string.
+//!
+//! How far to shorten the strings is computed from their serialized lengths, so content
+//! over its limit is serialized only once more, already within it.
+
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// Appended to every shortened string
+const TRUNCATION_SUFFIX: &str = "...[truncated]";
+
+/// Serialize `content` within `max_bytes`, returning the serialized content and whether it
+/// had to be truncated
+pub(crate) fn serialize_within(content: &Value, max_bytes: usize) -> (String, bool) {
+    let serialized = content.to_string();
+    if serialized.len() <= max_bytes {
+        return (serialized, false);
+    }
+
+    let mut lengths = Vec::new();
+    string_lengths(content, &mut lengths);
+    if let Some(cap) = longest_cap(&lengths, serialized.len() - max_bytes) {
+        let truncated = cap_strings(content, cap).to_string();
+        if truncated.len() <= max_bytes {
+            return (truncated, true);
+        }
+    }
+
+    // Even empty strings don't fit: keep the beginning of the serialized JSON as a string
+    (truncated_string(&serialized, max_bytes), true)
+}
+
+/// The longest cap on the serialized length of strings (see [`cap_strings`]) that saves
+/// at least `excess` bytes, if any does
+fn longest_cap(lengths: &[usize], excess: usize) -> Option<usize> {
+    // A string over the cap shrinks to at most the cap plus the suffix
+    let savings = |cap: usize| -> usize {
+        lengths
+            .iter()
+            .map(|len| len.saturating_sub(cap + TRUNCATION_SUFFIX.len()))
+            .sum()
+    };
+    if savings(0) < excess {
+        return None;
+    }
+    // Savings decrease as the cap grows: find the last one saving enough
+    let (mut low, mut high) = (0, lengths.iter().copied().max().unwrap_or(0));
+    while low < high {
+        let cap = low + (high - low).div_ceil(2);
+        if savings(cap) >= excess {
+            low = cap;
+        } else {
+            high = cap - 1;
+        }
+    }
+    Some(low)
+}
+
+/// The beginning of `serialized` as a JSON string of at most `max_bytes`, ending in the
+/// truncation suffix if that fits. Under two bytes not even an empty JSON string fits, and
+/// `serialized` is cut as is.
+fn truncated_string(serialized: &str, max_bytes: usize) -> String {
+    if max_bytes < 2 {
+        return cut(serialized, max_bytes).to_string();
+    }
+    let suffix = match max_bytes >= 2 + TRUNCATION_SUFFIX.len() {
+        true => TRUNCATION_SUFFIX,
+        false => "",
+    };
+    let prefix = cut_serialized(serialized, max_bytes - 2 - suffix.len());
+    Value::String(format!("{}{}", prefix, suffix)).to_string()
+}
+
+/// `s` within `max_bytes`: shortened and marked as truncated if it is longer
+pub(crate) fn truncate_str(s: &str, max_bytes: usize) -> Cow<'_, str> {
+    if s.len() <= max_bytes {
+        Cow::Borrowed(s)
+    } else if max_bytes < TRUNCATION_SUFFIX.len() {
+        Cow::Borrowed(cut(s, max_bytes))
+    } else {
+        let prefix = cut(s, max_bytes - TRUNCATION_SUFFIX.len());
+        Cow::Owned(format!("{}{}", prefix, TRUNCATION_SUFFIX))
+    }
+}
+
+/// Collect the serialized length in bytes (without quotes) of every string in `value`
+fn string_lengths(value: &Value, lengths: &mut Vec<usize>) {
+    match value {
+        Value::String(s) => lengths.push(serialized_len(s)),
+        Value::Array(items) => items.iter().for_each(|item| string_lengths(item, lengths)),
+        Value::Object(fields) => fields
+            .values()
+            .for_each(|field| string_lengths(field, lengths)),
+        _ => {}
+    }
+}
+
+/// A copy of `value` with every string serialized longer than `cap` bytes (and the suffix
+/// marking it as truncated) shortened to it
+fn cap_strings(value: &Value, cap: usize) -> Value {
+    match value {
+        Value::String(s) if serialized_len(s) > cap + TRUNCATION_SUFFIX.len() => {
+            Value::String(format!("{}{}", cut_serialized(s, cap), TRUNCATION_SUFFIX))
+        }
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|item| cap_strings(item, cap)).collect())
+        }
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, field)| (key.clone(), cap_strings(field, cap)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+/// The longest prefix of `s` of at most `max_bytes`, cut at a character boundary
+fn cut(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// The longest prefix of `s` serialized in at most `max_bytes` (without quotes)
+fn cut_serialized(s: &str, max_bytes: usize) -> &str {
+    let mut len = 0;
+    for (i, c) in s.char_indices() {
+        len += escaped_len(c);
+        if len > max_bytes {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+/// Length in bytes of `s` serialized as a JSON string, without quotes
+fn serialized_len(s: &str) -> usize {
+    s.chars().map(escaped_len).sum()
+}
+
+/// Length in bytes of a character in a serialized JSON string
+fn escaped_len(c: char) -> usize {
+    match c {
+        '"' | '\\' | '\n' | '\r' | '\t' | '\u{8}' | '\u{c}' => 2,
+        '\0'..='\u{1f}' => 6,
+        c => c.len_utf8(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_content_within_limit_is_untouched() {
+        let content = json!({"messages": [{"role": "user", "content": "Hi"}]});
+        assert_eq!(
+            serialize_within(&content, 1000),
+            (content.to_string(), false)
+        );
+    }
+
+    #[test]
+    fn test_longest_strings_are_shortened_first() {
+        let image = "A".repeat(10_000);
+        let content = json!({"messages": [
+            {"role": "system", "content": "Be brief"},
+            {"role": "user", "content": [
+                {"type": "text", "text": "What is this?"},
+                {"type": "image_url", "image_url": {"url": format!("data:image/png;base64,{}", image)}}
+            ]}
+        ]});
+
+        let (truncated, was_truncated) = serialize_within(&content, 400);
+        assert!(was_truncated);
+        assert!(truncated.len() <= 400);
+        let truncated: Value = serde_json::from_str(&truncated).unwrap();
+        assert_eq!(truncated["messages"][0]["content"], "Be brief");
+        assert_eq!(
+            truncated["messages"][1]["content"][0]["text"],
+            "What is this?"
+        );
+        let url = truncated["messages"][1]["content"][1]["image_url"]["url"]
+            .as_str()
+            .unwrap();
+        assert!(url.starts_with("data:image/png;base64,AAA"));
+        assert!(url.ends_with(TRUNCATION_SUFFIX));
+    }
+
+    #[test]
+    fn test_multibyte_strings_stay_valid() {
+        let content = json!({"text": "é".repeat(1000)});
+        let (truncated, was_truncated) = serialize_within(&content, 101);
+        assert!(was_truncated);
+        assert!(truncated.len() <= 101);
+        let truncated: Value = serde_json::from_str(&truncated).unwrap();
+        assert!(truncated["text"].as_str().unwrap().starts_with("éé"));
+    }
+
+    #[test]
+    fn test_oversized_structure_becomes_a_string() {
+        let content = Value::Array((0..1000).map(|i| json!({"index": i})).collect());
+        let (truncated, was_truncated) = serialize_within(&content, 50);
+        assert!(was_truncated);
+        assert!(truncated.len() <= 50);
+        let truncated: Value = serde_json::from_str(&truncated).unwrap();
+        assert!(truncated.as_str().unwrap().starts_with("[{\""));
+    }
+
+    #[test]
+    fn test_tiny_limits_are_respected() {
+        let content = json!({"text": "\"quoted\" ".repeat(20)});
+        for max_bytes in 0..20 {
+            let (truncated, was_truncated) = serialize_within(&content, max_bytes);
+            assert!(was_truncated);
+            assert!(
+                truncated.len() <= max_bytes,
+                "{} > {}",
+                truncated,
+                max_bytes
+            );
+            if max_bytes >= 2 {
+                assert!(
+                    serde_json::from_str::<Value>(&truncated).is_ok(),
+                    "{}",
+                    truncated
+                );
+            }
+        }
+        // Within the limit exactly, without room for the suffix
+        assert_eq!(serialize_within(&content, 12).0, r#""{\"text\":""#);
+    }
+
+    #[test]
+    fn test_escaped_strings_fit() {
+        let content = json!({"text": "\"".repeat(500), "other": "x".repeat(500)});
+        let (truncated, _) = serialize_within(&content, 300);
+        assert!(truncated.len() <= 300);
+        let truncated: Value = serde_json::from_str(&truncated).unwrap();
+        assert!(truncated["other"].as_str().unwrap().starts_with("xxx"));
+    }
+}
@@ -0,0 +1,256 @@
+//! Parsing of the `usage` object of responses
+//!
+//! OpenAI reports usage in several shapes: `prompt_tokens`/`completion_tokens` with
+//! `*_tokens_details` for chat completions and embeddings, `input_tokens`/`output_tokens`
+//! for the Responses, image and audio APIs, and seconds of audio for whisper
+//! transcriptions. All are normalized into the Langfuse usage details map, where each
+//! detail (e.g. `input_cached_tokens`) is reported separately from, and subtracted from,
+//! the plain `input`/`output` counts so Langfuse prices every kind of token once.
+
+use opentelemetry::KeyValue;
+use opentelemetry_semantic_conventions::attribute::{
+    GEN_AI_USAGE_INPUT_TOKENS, GEN_AI_USAGE_OUTPUT_TOKENS,
+};
+use serde_json::{Map, Value};
+
+/// Input tokens served from the prompt cache
+pub(crate) const GEN_AI_USAGE_CACHE_READ_INPUT_TOKENS: &str =
+    "gen_ai.usage.cache_read.input_tokens";
+/// Output tokens spent on (hidden) reasoning
+pub(crate) const GEN_AI_USAGE_REASONING_OUTPUT_TOKENS: &str =
+    "gen_ai.usage.reasoning.output_tokens";
+/// Audio input tokens
+pub(crate) const OPENAI_USAGE_AUDIO_INPUT_TOKENS: &str = "openai.usage.audio.input_tokens";
+/// Audio output tokens
+pub(crate) const OPENAI_USAGE_AUDIO_OUTPUT_TOKENS: &str = "openai.usage.audio.output_tokens";
+/// Predicted output tokens that appeared in the completion
+pub(crate) const OPENAI_USAGE_ACCEPTED_PREDICTION_TOKENS: &str =
+    "openai.usage.accepted_prediction_tokens";
+/// Predicted output tokens that did not appear in the completion (still billed)
+pub(crate) const OPENAI_USAGE_REJECTED_PREDICTION_TOKENS: &str =
+    "openai.usage.rejected_prediction_tokens";
+
+/// Normalized token usage of a response
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Usage {
+    pub(crate) input: Option<i64>,
+    pub(crate) output: Option<i64>,
+    pub(crate) total: Option<i64>,
+    /// Langfuse usage details: `input`, `output`, `total` and the individual details
+    pub(crate) details: Map<String, Value>,
+}
+
+impl Usage {
+    /// Parse the `usage` object of a response
+    pub(crate) fn parse(usage: &Value) -> Self {
+        // Whisper models are billed per second of audio
+        if usage.get("type").and_then(|t| t.as_str()) == Some("duration") {
+            let mut details = Map::new();
+            if let Some(seconds) = usage.get("seconds").filter(|s| s.is_number()) {
+                details.insert("input_audio_seconds".to_string(), seconds.clone());
+            }
+            return Self {
+                details,
+                ..Self::default()
+            };
+        }
+
+        let input = usage
+            .get("prompt_tokens")
+            .or_else(|| usage.get("input_tokens"))
+            .and_then(|v| v.as_i64());
+        let output = usage
+            .get("completion_tokens")
+            .or_else(|| usage.get("output_tokens"))
+            .and_then(|v| v.as_i64());
+        let total = usage.get("total_tokens").and_then(|v| v.as_i64());
+
+        let mut details = Map::new();
+        let input_details = collect_details(
+            usage,
+            // Audio transcriptions use the singular `input_token_details`
+            &[
+                "prompt_tokens_details",
+                "input_tokens_details",
+                "input_token_details",
+            ],
+            "input",
+            &mut details,
+        );
+        let output_details = collect_details(
+            usage,
+            &["completion_tokens_details", "output_tokens_details"],
+            "output",
+            &mut details,
+        );
+
+        // Details are part of the input/output counts; report only the remainder as plain
+        // input/output so no token is counted twice
+        if let Some(input) = input {
+            details.insert("input".to_string(), (input - input_details).max(0).into());
+        }
+        if let Some(output) = output {
+            details.insert(
+                "output".to_string(),
+                (output - output_details).max(0).into(),
+            );
+        }
+        if let Some(total) = total {
+            details.insert("total".to_string(), total.into());
+        }
+
+        Self {
+            input,
+            output,
+            total,
+            details,
+        }
+    }
+
+    /// A usage detail, e.g. `input_cached_tokens`
+    pub(crate) fn detail(&self, key: &str) -> Option<i64> {
+        self.details.get(key).and_then(|v| v.as_i64())
+    }
+
+    /// Span attributes of the semantic conventions: the token counts, and the individual
+    /// details that have a dedicated attribute
+    pub(crate) fn semconv_attributes(&self) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        if let Some(input) = self.input {
+            attributes.push(KeyValue::new(GEN_AI_USAGE_INPUT_TOKENS, input));
+        }
+        if let Some(output) = self.output {
+            attributes.push(KeyValue::new(GEN_AI_USAGE_OUTPUT_TOKENS, output));
+        }
+
+        for (detail, attribute) in [
+            ("input_cached_tokens", GEN_AI_USAGE_CACHE_READ_INPUT_TOKENS),
+            (
+                "output_reasoning_tokens",
+                GEN_AI_USAGE_REASONING_OUTPUT_TOKENS,
+            ),
+            ("input_audio_tokens", OPENAI_USAGE_AUDIO_INPUT_TOKENS),
+            ("output_audio_tokens", OPENAI_USAGE_AUDIO_OUTPUT_TOKENS),
+            (
+                "output_accepted_prediction_tokens",
+                OPENAI_USAGE_ACCEPTED_PREDICTION_TOKENS,
+            ),
+            (
+                "output_rejected_prediction_tokens",
+                OPENAI_USAGE_REJECTED_PREDICTION_TOKENS,
+            ),
+        ] {
+            if let Some(value) = self.detail(detail) {
+                attributes.push(KeyValue::new(attribute, value));
+            }
+        }
+        attributes
+    }
+}
+
+/// Copy the non-zero entries of the first present details object into `details` with the
+/// given prefix, returning their sum
+fn collect_details(
+    usage: &Value,
+    keys: &[&str],
+    prefix: &str,
+    details: &mut Map<String, Value>,
+) -> i64 {
+    let Some(entries) = keys
+        .iter()
+        .find_map(|key| usage.get(*key).and_then(|d| d.as_object()))
+    else {
+        return 0;
+    };
+
+    let mut counted = 0;
+    for (key, value) in entries {
+        let Some(count) = value.as_i64() else {
+            continue;
+        };
+        // Image and transcription input is split into text and image/audio tokens; the
+        // text tokens are what remains as plain input
+        if count == 0 || key == "text_tokens" {
+            continue;
+        }
+        details.insert(format!("{}_{}", prefix, key), count.into());
+        counted += count;
+    }
+    counted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_chat_usage_details() {
+        let usage = Usage::parse(&json!({
+            "prompt_tokens": 2006,
+            "completion_tokens": 300,
+            "total_tokens": 2306,
+            "prompt_tokens_details": {"cached_tokens": 1920, "audio_tokens": 0},
+            "completion_tokens_details": {
+                "reasoning_tokens": 192,
+                "audio_tokens": 0,
+                "accepted_prediction_tokens": 10,
+                "rejected_prediction_tokens": 5
+            }
+        }));
+
+        assert_eq!(usage.input, Some(2006));
+        assert_eq!(usage.output, Some(300));
+        assert_eq!(
+            Value::Object(usage.details.clone()),
+            json!({
+                "input": 86,
+                "input_cached_tokens": 1920,
+                "output": 93,
+                "output_reasoning_tokens": 192,
+                "output_accepted_prediction_tokens": 10,
+                "output_rejected_prediction_tokens": 5,
+                "total": 2306
+            })
+        );
+
+        let attributes = usage.semconv_attributes();
+        assert!(attributes.contains(&KeyValue::new(GEN_AI_USAGE_INPUT_TOKENS, 2006i64)));
+        assert!(attributes.contains(&KeyValue::new(
+            GEN_AI_USAGE_CACHE_READ_INPUT_TOKENS,
+            1920i64
+        )));
+        assert!(attributes.contains(&KeyValue::new(GEN_AI_USAGE_REASONING_OUTPUT_TOKENS, 192i64)));
+    }
+
+    #[test]
+    fn test_embedding_and_image_usage() {
+        let embedding = Usage::parse(&json!({"prompt_tokens": 8, "total_tokens": 8}));
+        assert_eq!(embedding.output, None);
+        assert_eq!(
+            Value::Object(embedding.details),
+            json!({"input": 8, "total": 8})
+        );
+
+        // gpt-image-1 splits the input into text and image tokens
+        let image = Usage::parse(&json!({
+            "input_tokens": 50,
+            "output_tokens": 4160,
+            "total_tokens": 4210,
+            "input_tokens_details": {"text_tokens": 40, "image_tokens": 10}
+        }));
+        assert_eq!(image.detail("input_image_tokens"), Some(10));
+        assert_eq!(image.detail("input_text_tokens"), None);
+        assert_eq!(image.detail("input"), Some(40));
+    }
+
+    #[test]
+    fn test_audio_duration_usage() {
+        let usage = Usage::parse(&json!({"type": "duration", "seconds": 42}));
+        assert_eq!(usage.input, None);
+        assert_eq!(
+            Value::Object(usage.details),
+            json!({"input_audio_seconds": 42})
+        );
+    }
+}